@@ -1,2 +1,3 @@
+pub mod conflict;
 pub mod dag;
 pub mod graph;
@@ -0,0 +1,287 @@
+/*
+ This file plans removals and deconfigurations caused by `Conflicts` and `Breaks` fields.
+
+ - `Conflicts`: conflicting installed packages must be removed before unpacking.
+   If the conflicting package is also `Replaces`-ed, dpkg removes it by itself while unpacking.
+ - `Breaks`: broken installed packages are deconfigured by dpkg while unpacking.
+ Conflicts among packages in the same transaction can't be solved.
+*/
+
+use crate::{
+  dpkg::status::DpkgStatusStatus,
+  package::{
+    client::PackageWithSource,
+    package::{Depends, Package},
+  },
+};
+
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConflictError {
+  #[error("{package:?} conflicts with {conflicting:?}, both of which are to be installed.")]
+  ConflictInTransaction {
+    package: String,
+    conflicting: String,
+  },
+
+  #[error("{package:?} breaks {broken:?}, both of which are to be installed.")]
+  BreakInTransaction { package: String, broken: String },
+
+  #[error("installed {package:?} breaks {broken:?}, which is to be installed.")]
+  BrokenByInstalled { package: String, broken: String },
+
+  #[error("{dependent:?} depends on {removed:?}, which is to be removed due to conflicts.")]
+  BrokenDependent { dependent: String, removed: String },
+}
+
+// installed package to be removed.
+#[derive(Debug, Clone)]
+pub struct Removal {
+  pub package: Package,
+  pub cause: String,  // name of package which conflicts with `package`
+  pub replaced: bool, // if true, dpkg removes `package` by itself while unpacking
+}
+
+// installed package to be deconfigured.
+#[derive(Debug, Clone)]
+pub struct Deconfiguration {
+  pub package: Package,
+  pub cause: String, // name of package which breaks `package`
+}
+
+#[derive(Debug, Default)]
+pub struct ConflictPlan {
+  pub removals: Vec<Removal>,
+  pub deconfigurations: Vec<Deconfiguration>,
+}
+
+impl ConflictPlan {
+  // names of packages in transaction which break installed packages.
+  pub fn breaking_packages(&self) -> Vec<String> {
+    self
+      .deconfigurations
+      .iter()
+      .map(|deconf| deconf.cause.clone())
+      .collect()
+  }
+
+  // removals which must be requested to dpkg explicitly before unpacking.
+  pub fn explicit_removals(&self) -> Vec<&Removal> {
+    self
+      .removals
+      .iter()
+      .filter(|removal| !removal.replaced)
+      .collect()
+  }
+}
+
+fn relation_hits(relations: &[Depends], target: &Package) -> bool {
+  relations.iter().any(|rel| rel.is_satisfied_by(target))
+}
+
+// Plan removals and deconfigurations to install `transaction` onto `installeds`.
+// `installeds` is dpkg status DB, which can include not-installed packages.
+pub fn plan_conflicts(
+  transaction: &[PackageWithSource],
+  installeds: &HashSet<Package>,
+) -> Result<ConflictPlan, ConflictError> {
+  let mut plan = ConflictPlan::default();
+  let transaction_names: Vec<&str> = transaction
+    .iter()
+    .map(|pws| pws.package.name.as_str())
+    .collect();
+  // installed packages which are not upgraded in this transaction.
+  let installeds: Vec<&Package> = installeds
+    .iter()
+    .filter(|installed| {
+      matches!(&installed.status, Some(status) if status.status == DpkgStatusStatus::Installed)
+        && !transaction_names.contains(&installed.name.as_str())
+    })
+    .collect();
+
+  for pws in transaction {
+    let package = &pws.package;
+
+    // conflicts/breaks among packages in transaction.
+    for other in transaction {
+      let other = &other.package;
+      if other.name == package.name {
+        continue;
+      }
+      if relation_hits(&package.conflicts, other) {
+        return Err(ConflictError::ConflictInTransaction {
+          package: package.name.clone(),
+          conflicting: other.name.clone(),
+        });
+      }
+      if relation_hits(&package.breaks, other) {
+        return Err(ConflictError::BreakInTransaction {
+          package: package.name.clone(),
+          broken: other.name.clone(),
+        });
+      }
+    }
+
+    // conflicts/breaks against installed packages.
+    for installed in &installeds {
+      if relation_hits(&package.conflicts, installed)
+        || relation_hits(&installed.conflicts, package)
+      {
+        if !plan
+          .removals
+          .iter()
+          .any(|removal| removal.package.name == installed.name)
+        {
+          plan.removals.push(Removal {
+            package: (*installed).clone(),
+            cause: package.name.clone(),
+            replaced: relation_hits(&package.replaces, installed),
+          });
+        }
+        continue;
+      }
+      if relation_hits(&package.breaks, installed) {
+        if !plan
+          .deconfigurations
+          .iter()
+          .any(|deconf| deconf.package.name == installed.name)
+        {
+          plan.deconfigurations.push(Deconfiguration {
+            package: (*installed).clone(),
+            cause: package.name.clone(),
+          });
+        }
+        continue;
+      }
+      if relation_hits(&installed.breaks, package) {
+        return Err(ConflictError::BrokenByInstalled {
+          package: installed.name.clone(),
+          broken: package.name.clone(),
+        });
+      }
+    }
+  }
+
+  // check remaining packages don't depend on removed packages.
+  let removed_names: Vec<&str> = plan
+    .removals
+    .iter()
+    .map(|removal| removal.package.name.as_str())
+    .collect();
+  let remainings: Vec<&Package> = installeds
+    .iter()
+    .filter(|installed| !removed_names.contains(&installed.name.as_str()))
+    .copied()
+    .chain(transaction.iter().map(|pws| &pws.package))
+    .collect();
+  for dependent in &installeds {
+    if removed_names.contains(&dependent.name.as_str()) {
+      continue;
+    }
+    for anyof in &dependent.depends {
+      let satisfied = remainings
+        .iter()
+        .any(|remaining| anyof.is_satisfied_by(remaining));
+      if satisfied {
+        continue;
+      }
+      if let Some(removal) = plan
+        .removals
+        .iter()
+        .find(|removal| anyof.targets(&removal.package))
+      {
+        return Err(ConflictError::BrokenDependent {
+          dependent: dependent.name.clone(),
+          removed: removal.package.name.clone(),
+        });
+      }
+    }
+  }
+
+  Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dpkg::status::DpkgStatusArea;
+  use crate::package::{package::*, version::Version};
+  use crate::source::source::{ArchivedType, Component, Source};
+
+  fn new_package(name: &str, version: &str) -> Package {
+    Package {
+      name: name.into(),
+      version: Version::from(version).unwrap(),
+      ..Default::default()
+    }
+  }
+
+  fn installed(mut package: Package) -> Package {
+    package.status = Some(DpkgStatusArea::from("install ok installed"));
+    package
+  }
+
+  fn to_pws(package: Package) -> PackageWithSource {
+    PackageWithSource {
+      package,
+      source: Source {
+        archive_type: ArchivedType::DEB,
+        url: "http://test".into(),
+        distro: "/".into(),
+        component: Component::NULL,
+        options: Default::default(),
+        mirrors: vec![],
+      },
+      dpkg_status: None,
+    }
+  }
+
+  #[test]
+  fn test_plan_conflicts() {
+    // `new` conflicts with `old`, replaces `old2`, and breaks `lib (<< 2.0)`.
+    let mut new = new_package("new", "1.0-1");
+    new.conflicts = DependsAnyOf::from("old, old2", DepType::Conflicts)
+      .unwrap()
+      .into_iter()
+      .flat_map(|anyof| anyof.depends)
+      .collect();
+    new.replaces = vec![Depends {
+      package: "old2".into(),
+      ..Default::default()
+    }];
+    new.breaks = DependsAnyOf::from("lib (<< 2.0)", DepType::Breaks).unwrap()[0]
+      .depends
+      .clone();
+    let installeds: HashSet<Package> = vec![
+      installed(new_package("old", "1.0-1")),
+      installed(new_package("old2", "1.0-1")),
+      installed(new_package("lib", "1.0-1")),
+    ]
+    .into_iter()
+    .collect();
+
+    let plan = plan_conflicts(&[to_pws(new.clone())], &installeds).unwrap();
+    assert_eq!(plan.removals.len(), 2);
+    assert_eq!(plan.explicit_removals().len(), 1);
+    assert_eq!(plan.explicit_removals()[0].package.name, "old");
+    assert_eq!(plan.deconfigurations.len(), 1);
+    assert_eq!(plan.breaking_packages(), vec!["new"]);
+
+    // conflicts in the same transaction can't be solved.
+    let result = plan_conflicts(
+      &[to_pws(new.clone()), to_pws(new_package("old", "1.0-1"))],
+      &HashSet::new(),
+    );
+    assert!(result.is_err());
+
+    // installed package depending on removed one can't be left.
+    let mut dependent = installed(new_package("dependent", "1.0-1"));
+    dependent.depends = DependsAnyOf::from("old", DepType::Depends).unwrap();
+    let mut installeds = installeds;
+    installeds.insert(dependent);
+    let result = plan_conflicts(&[to_pws(new)], &installeds);
+    assert!(matches!(result, Err(ConflictError::BrokenDependent { .. })));
+  }
+}
@@ -82,7 +82,7 @@ impl Graph {
     let root_ix = self
       .nodes
       .iter()
-      .position(|node| node.package.name == root)
+      .position(|node| node.package.full_name() == root)
       .unwrap();
     self.dfs_internal(root_ix);
   }
@@ -119,13 +119,15 @@ impl Graph {
   }
 
   // Decomposition of Strongly Connected Components, to create DAG.
-  fn scc(&mut self, root: &str) -> Result<(), DagError> {
+  fn scc(&mut self, roots: &[&str]) -> Result<(), DagError> {
     self.clear_visited();
     self.check_before_scc_valid()?;
 
     // first, DFS in home-way order
-    // NOTE: `node.normal_index` remains -1 if the node is unreachable from root node.
-    self.dfs_root(root);
+    // NOTE: `node.normal_index` remains -1 if the node is unreachable from all root nodes.
+    for root in roots {
+      self.dfs_root(root);
+    }
 
     // second, reverse DFS and make groups (just ignore unreachable node)
     self.clear_visited();
@@ -235,20 +237,20 @@ fn construct_nodes(packages: Vec<Package>) -> Result<Graph, DagError> {
     .collect();
 
   // assign TOs and reverse TOs
+  // recommended packages are also ordered before recommending ones, so that they are reachable.
   for ix in 0..nodes.len() {
-    let depends: Vec<Depends> = nodes[ix]
-      .package
+    let package = &nodes[ix].package;
+    let depends: Vec<DependsAnyOf> = package
       .depends
       .iter()
-      .map(|dep_anys| {
-        // XXX choose arbitrary dep. Should choose the most loose dependency?
-        dep_anys.depends[0].clone()
-      })
+      .chain(package.recommends.iter())
+      .map(|dep| dep.bound_to(package))
       .collect();
     for dep in depends {
+      // alternative chosen by the resolver is the one included in nodes.
       let cand = match nodes
         .iter()
-        .position(|package_nodes| package_nodes.package.name == dep.package)
+        .position(|package_nodes| dep.targets(&package_nodes.package))
       {
         Some(i) => i,
         None => continue, // just ignore when target is not found
@@ -282,13 +284,14 @@ fn sanity_check(deps: &Vec<PackageWithSource>) -> Result<(), DagError> {
       .filter(|anyof| anyof.depends[0].dep_type == DepType::PreDepends)
       .collect();
     for depended_on in depended_ons {
+      let depended_on = depended_on.bound_to(&package.package);
       if let Some(depended_on_ix) = deps
         .iter()
-        .position(|pws| pws.package.name == depended_on.depends[0].package)
+        .position(|pws| depended_on.targets(&pws.package))
       {
         if depended_on_ix < ix {
           return Err(DagError::FailedToResolve {
-            pre_depended_on: depended_on.depends[0].package.to_string(),
+            pre_depended_on: deps[depended_on_ix].package.name.to_string(),
             pre_depending: package.package.name.to_string(),
           });
         }
@@ -311,6 +314,7 @@ fn remove_normal_deps(packages: &mut Vec<Package>) {
     for jx in target_jx.into_iter().rev() {
       packages[ix].depends.remove(jx);
     }
+    packages[ix].recommends.clear();
   }
 }
 
@@ -326,10 +330,8 @@ fn force_predepends_same_group(nodes: &mut Vec<&PackageNode>) {
         continue;
       }
       // if the node has pre-depends in the same group, place pre-depended-on node after pre-depending node.
-      if let Some(jx) = nodes
-        .iter()
-        .position(|node| node.package.name == anyof.depends[0].package)
-      {
+      let anyof = anyof.bound_to(&node.package);
+      if let Some(jx) = nodes.iter().position(|node| anyof.targets(&node.package)) {
         orders.push(jx);
       }
     }
@@ -354,10 +356,10 @@ fn force_predepends_same_group(nodes: &mut Vec<&PackageNode>) {
 //      If depended-on package is not found in `deps`, this function just ignores it.
 //      (cuz it would happen when already-installed packages are removed from `deps`.)
 // NOTE: result is returned in reversed-order of deps.
-// NOTE: if there are unreachable nodes from `root` node, these nodes are omitted in returned vec.
+// NOTE: if there are unreachable nodes from all `roots`, these nodes are omitted in returned vec.
 fn sort_depends_internal(
   deps: HashSet<PackageWithSource>,
-  root: &str,
+  roots: &[&str],
   dep_type: DepType,
 ) -> Result<Vec<PackageWithSource>, DagError> {
   let mut packages: Vec<Package> = deps.iter().map(|pws| pws.package.clone()).collect();
@@ -368,30 +370,38 @@ fn sort_depends_internal(
   let mut graph = Graph::from(packages).unwrap();
 
   // do SCC to make a DAG and do topological sort
-  graph.scc(root).unwrap();
+  graph.scc(roots).unwrap();
   graph.topological_sort();
 
   let mut results = vec![];
-  let group_orders = graph.topological_order;
+  // groups in the topological order, where depended-on groups come first.
+  let mut group_orders: Vec<i64> = (0..graph.group_num).collect();
+  group_orders.sort_by_key(|group_id| graph.topological_order[*group_id as usize]);
 
   // NOTE: if target package itself has circular dependencies,
   //      at least the target package should be installed at the end.
-  let target_group_id = graph
-    .nodes
-    .iter()
-    .find(|node| node.package.name == root)
-    .unwrap()
-    .group;
+  //      Multiple targets can depend on each other, so they just follow the topological order.
+  let target_group_id = match roots {
+    [root] => Some(
+      graph
+        .nodes
+        .iter()
+        .find(|node| node.package.full_name() == *root)
+        .unwrap()
+        .group,
+    ),
+    _ => None,
+  };
 
   for group_id in group_orders {
-    if group_id as i64 == target_group_id {
+    if Some(group_id) == target_group_id {
       // ignore the group including target package
       continue;
     }
     let mut nodes: Vec<&PackageNode> = graph
       .nodes
       .iter()
-      .filter(|node| node.group == group_id as i64)
+      .filter(|node| node.group == group_id)
       .collect();
     force_predepends_same_group(&mut nodes);
 
@@ -420,7 +430,7 @@ fn sort_depends_internal(
   for node in graph
     .nodes
     .iter()
-    .filter(|node| node.group == target_group_id)
+    .filter(|node| Some(node.group) == target_group_id)
   {
     let pws = deps
       .iter()
@@ -428,7 +438,7 @@ fn sort_depends_internal(
       .unwrap()
       .clone();
     // if node is target package itself, add it at the end.
-    if pws.package.name == root {
+    if pws.package.full_name() == roots[0] {
       let tmp = target_results.clone();
       target_results = vec![pws];
       target_results.extend(tmp);
@@ -451,7 +461,19 @@ pub fn sort_depends(
   deps: HashSet<PackageWithSource>,
   root: &str,
 ) -> Result<Vec<PackageWithSource>, DagError> {
-  sort_depends_internal(deps, root, DepType::Depends)
+  sort_depends_internal(deps, &[root], DepType::Depends)
+}
+
+// Sort packages dependencies reachable from any of `roots` in topological way.
+pub fn sort_depends_multi(
+  deps: HashSet<PackageWithSource>,
+  roots: &[String],
+) -> Result<Vec<PackageWithSource>, DagError> {
+  if deps.is_empty() {
+    return Ok(vec![]);
+  }
+  let roots: Vec<&str> = roots.iter().map(String::as_str).collect();
+  sort_depends_internal(deps, &roots, DepType::Depends)
 }
 
 // Returns layered packages.
@@ -465,10 +487,8 @@ pub fn split_layers(pwss: &[PackageWithSource]) -> Vec<Vec<PackageWithSource>> {
     let package = &pws.package;
     for anyof in &package.depends {
       if anyof.depends[0].dep_type == DepType::PreDepends {
-        if let Some(target) = pwss
-          .iter()
-          .position(|pws| pws.package.name == anyof.depends[0].package)
-        {
+        let anyof = anyof.bound_to(package);
+        if let Some(target) = pwss.iter().position(|pws| anyof.targets(&pws.package)) {
           depended_on_ixs.push(target);
         }
       }
@@ -549,7 +569,7 @@ mod tests {
     graph.dfs_root("0");
     assert_eq!(to_orders(&graph), order);
 
-    assert_eq!(graph.scc("0").is_err(), true);
+    assert_eq!(graph.scc(&["0"]).is_err(), true);
   }
 
   #[test]
@@ -593,7 +613,7 @@ mod tests {
     answer_groups.insert(5, vec![7]);
     answer_groups.insert(6, vec![8, 9]);
 
-    graph.scc("0").unwrap();
+    graph.scc(&["0"]).unwrap();
     let groups = to_groups(&graph);
     assert_eq!(groups, answer_groups);
 
@@ -669,4 +689,47 @@ mod tests {
       .unwrap();
     assert_eq!(canberra_ix < freedesktop_ix, true);
   }
+
+  #[test]
+  fn test_sort_multi_roots() {
+    // `tool` depends on `app`, which is also a root.
+    let packages = depstr2packages(
+      "
+      app: libfoo
+      tool: app, libbar
+      libfoo: libc6
+      libbar: libc6
+      libc6:
+      unrelated: libc6
+    ",
+    );
+    let source = Source::from(
+      ArchivedType::DEB,
+      "http://hoge",
+      "focal",
+      vec![Component::MAIN],
+    )
+    .into_iter()
+    .next()
+    .unwrap();
+    let pwss: HashSet<PackageWithSource> = packages
+      .into_iter()
+      .map(|package| PackageWithSource {
+        package,
+        source: source.clone(),
+        dpkg_status: None,
+      })
+      .collect();
+
+    let sorted = sort_depends_multi(pwss, &["app".into(), "tool".into()]).unwrap();
+    let names: Vec<&str> = sorted.iter().map(|pws| pws.package.name.as_str()).collect();
+    let position = |name: &str| names.iter().position(|n| *n == name).unwrap();
+    // unreachable ones from all roots are omitted.
+    assert_eq!(names.len(), 5);
+    assert!(position("tool") < position("app"));
+    assert!(position("app") < position("libfoo"));
+    assert!(position("tool") < position("libbar"));
+    assert!(position("libfoo") < position("libc6"));
+    assert!(position("libbar") < position("libc6"));
+  }
 }
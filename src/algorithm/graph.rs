@@ -31,7 +31,7 @@ impl Graph {
     let mut nodes = HashMap::new();
     for (ix, package) in packages.iter().enumerate() {
       nodes.insert(
-        package.name.clone(),
+        package.full_name(),
         Node {
           package: package.clone(),
           to: vec![],
@@ -44,19 +44,44 @@ impl Graph {
       );
     }
 
+    // virtual package name -> names of its providers
+    let mut providers: HashMap<String, Vec<String>> = HashMap::new();
+    for package in &packages {
+      for provide in &package.provides {
+        providers
+          .entry(provide.package.clone())
+          .or_default()
+          .push(package.full_name());
+      }
+    }
+
     for ix in 0..packages.len() {
+      // every alternative (and providers of it) is kept alive by the depending package.
+      // recommended packages are kept alive as well as apt does.
       let deps: Vec<String> = packages[ix]
         .depends
         .iter()
-        .map(|anyof| anyof.depends[0].package.clone())
+        .chain(packages[ix].recommends.iter())
+        .flat_map(|anyof| anyof.bound_to(&packages[ix]).depends)
+        .flat_map(|dep| {
+          // native one is kept alive as well cuz it may be `Multi-Arch: foreign`.
+          let mut names = vec![dep.package.clone()];
+          if let Some(arch) = dep.required_arch() {
+            names.push(format!("{}:{}", dep.package, arch));
+          }
+          if let Some(provider_names) = providers.get(&dep.package) {
+            names.extend(provider_names.clone());
+          }
+          names
+        })
         .collect();
-      let depending_ix = nodes.get(&packages[ix].name).unwrap().index;
+      let depending_ix = nodes.get(&packages[ix].full_name()).unwrap().index;
       for dep in deps {
         if let Some(depended_node) = nodes.get_mut(&dep) {
           depended_node.revto.push(depending_ix);
 
           let depended_ix = depended_node.index;
-          let node = nodes.get_mut(&packages[ix].name).unwrap();
+          let node = nodes.get_mut(&packages[ix].full_name()).unwrap();
           node.to.push(depended_ix);
         }
       }
@@ -86,7 +111,7 @@ impl Graph {
     let root = self
       .nodes
       .iter()
-      .find(|(_, node)| node.package.name == root);
+      .find(|(_, node)| node.package.full_name() == root);
     // if root itself is not in Graph, return false
     if root.is_none() {
       return false;
@@ -128,7 +153,7 @@ impl Graph {
     let root = self
       .nodes
       .iter()
-      .find(|(_, node)| node.package.name == root);
+      .find(|(_, node)| node.package.full_name() == root);
     // if root itself is not in Graph, return false
     if root.is_none() {
       return vec![];
@@ -1 +1,3 @@
+pub mod config;
 pub mod extended_states;
+pub mod preferences;
@@ -0,0 +1,279 @@
+/*
+ This file defines client for IO of `/etc/apt/apt.conf` and files in `/etc/apt/apt.conf.d`.
+ Refer to apt.conf(5) for the syntax, such as:
+   Acquire::http::Proxy "http://proxy:3128";
+   Acquire::http { Proxy::mirror.example.com "DIRECT"; };
+ Only scalar options are kept, and list options such as `APT::NeverAutoRemove { "..."; };` are ignored.
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+  #[error("error in file IO")]
+  FileIoError(#[from] io::Error),
+
+  #[error("invalid apt.conf format in {file:?}: {msg:?}")]
+  InvalidFormat { file: String, msg: String },
+
+  #[error("invalid value of {key:?}: {value:?}")]
+  InvalidValue { key: String, value: String },
+}
+
+// options of apt.conf, whose keys are case-insensitive.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AptConfig {
+  values: HashMap<String, String>, // lowercased full key => value
+}
+
+impl AptConfig {
+  // read files in `apt.conf.d` in alphabetical order, and then `apt.conf` as apt does.
+  // Options in files read later override earlier ones.
+  pub fn read(source_dir: &Path) -> Result<Self, ConfigError> {
+    let mut config = Self::default();
+    for path in find_candidates(source_dir) {
+      let content = fs::read_to_string(&path)?;
+      config.parse(&content, &path)?;
+    }
+    Ok(config)
+  }
+
+  pub fn from(content: &str) -> Result<Self, ConfigError> {
+    let mut config = Self::default();
+    config.parse(content, Path::new(""))?;
+    Ok(config)
+  }
+
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.values.get(&key.to_lowercase()).map(String::as_str)
+  }
+
+  // options right under `prefix`, eg) `Acquire::http::Proxy` gives `(host, proxy)` of each host.
+  // Returned keys are lowercased.
+  pub fn children(&self, prefix: &str) -> Vec<(String, String)> {
+    let prefix = format!("{}::", prefix.to_lowercase());
+    let mut children: Vec<(String, String)> = self
+      .values
+      .iter()
+      .filter_map(|(key, value)| {
+        key
+          .strip_prefix(&prefix)
+          .filter(|rest| !rest.contains("::"))
+          .map(|rest| (rest.to_string(), value.clone()))
+      })
+      .collect();
+    children.sort();
+    children
+  }
+
+  fn parse(&mut self, content: &str, file: &Path) -> Result<(), ConfigError> {
+    let invalid = |msg: &str| ConfigError::InvalidFormat {
+      file: file.to_string_lossy().to_string(),
+      msg: msg.to_string(),
+    };
+    let tokens = tokenize(content).map_err(|msg| invalid(&msg))?;
+    let mut scopes: Vec<String> = vec![];
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+      match token {
+        Token::Word(key) => match tokens.next() {
+          Some(Token::Word(value)) | Some(Token::Str(value)) => {
+            if tokens.next() != Some(Token::Semicolon) {
+              return Err(invalid(&format!("missing `;` after {}", key)));
+            }
+            let mut full_key = scopes.clone();
+            full_key.push(key);
+            self
+              .values
+              .insert(full_key.join("::").to_lowercase(), value);
+          }
+          Some(Token::Open) => scopes.push(key),
+          _ => return Err(invalid(&format!("missing value of {}", key))),
+        },
+        // item of list option, which is not used by rapt2.
+        Token::Str(_) => {
+          if tokens.next() != Some(Token::Semicolon) {
+            return Err(invalid("missing `;` after list item"));
+          }
+        }
+        Token::Close => {
+          if scopes.pop().is_none() {
+            return Err(invalid("unbalanced `}`"));
+          }
+          if tokens.peek() == Some(&Token::Semicolon) {
+            tokens.next();
+          }
+        }
+        Token::Semicolon => {}
+        Token::Open => return Err(invalid("block without name")),
+      }
+    }
+    if !scopes.is_empty() {
+      return Err(invalid("unclosed `{`"));
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+  Word(String), // unquoted key or value
+  Str(String),  // quoted value
+  Open,
+  Close,
+  Semicolon,
+}
+
+// split `content` into tokens with skipping comments: `//`, `#` and `/* */`.
+fn tokenize(content: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = vec![];
+  let mut chars = content.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match c {
+      c if c.is_whitespace() => {}
+      '{' => tokens.push(Token::Open),
+      '}' => tokens.push(Token::Close),
+      ';' => tokens.push(Token::Semicolon),
+      '"' => {
+        let mut s = String::new();
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some(c) => s.push(c),
+            None => return Err("unterminated string".into()),
+          }
+        }
+        tokens.push(Token::Str(s));
+      }
+      // `#include` and `#clear` are also ignored.
+      '#' => {
+        for c in chars.by_ref() {
+          if c == '\n' {
+            break;
+          }
+        }
+      }
+      '/' if chars.peek() == Some(&'/') => {
+        for c in chars.by_ref() {
+          if c == '\n' {
+            break;
+          }
+        }
+      }
+      '/' if chars.peek() == Some(&'*') => {
+        chars.next();
+        let mut prev = ' ';
+        loop {
+          match chars.next() {
+            Some('/') if prev == '*' => break,
+            Some(c) => prev = c,
+            None => return Err("unterminated comment".into()),
+          }
+        }
+      }
+      c => {
+        let mut word = String::from(c);
+        while let Some(&c) = chars.peek() {
+          if c.is_whitespace() || matches!(c, '{' | '}' | ';' | '"') {
+            break;
+          }
+          word.push(c);
+          chars.next();
+        }
+        tokens.push(Token::Word(word));
+      }
+    }
+  }
+
+  Ok(tokens)
+}
+
+// find and return candidate config files.
+// Only files without extension or with `.conf` in `apt.conf.d` are valid as apt does.
+fn find_candidates(source_dir: &Path) -> Vec<PathBuf> {
+  let mut target_pathes = vec![];
+
+  if let Ok(candidates) = fs::read_dir(source_dir.join("apt.conf.d")) {
+    let mut pathes: Vec<PathBuf> = candidates
+      .flatten()
+      .map(|ent| ent.path())
+      .filter(|path| {
+        path.is_file()
+          && match path.extension() {
+            Some(ext) => ext == "conf",
+            None => true,
+          }
+      })
+      .collect();
+    pathes.sort();
+    target_pathes.extend(pathes);
+  }
+
+  let conf_path = source_dir.join("apt.conf");
+  if conf_path.is_file() {
+    target_pathes.push(conf_path);
+  }
+
+  target_pathes
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_apt_conf() {
+    let config = AptConfig::from(
+      r#"
+// proxy for CI
+Acquire::http::Proxy "http://proxy.example.com:3128/";
+Acquire::http {
+  Proxy::mirror.example.com "DIRECT"; # local mirror
+  User-Agent "rapt2-ci";
+};
+/* list options are ignored
+   even if they span lines */
+APT::NeverAutoRemove { "^linux-image.*"; };
+Acquire::Retries 5;
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+      config.get("acquire::http::proxy"),
+      Some("http://proxy.example.com:3128/")
+    );
+    assert_eq!(config.get("Acquire::http::User-Agent"), Some("rapt2-ci"));
+    assert_eq!(config.get("Acquire::Retries"), Some("5"));
+    assert_eq!(config.get("APT::NeverAutoRemove"), None);
+    assert_eq!(
+      config.children("Acquire::http::Proxy"),
+      vec![("mirror.example.com".to_string(), "DIRECT".to_string())]
+    );
+
+    assert!(AptConfig::from("Acquire::http::Proxy \"http://hoge\"").is_err());
+    assert!(AptConfig::from("Acquire { http { Proxy \"http://hoge\"; };").is_err());
+    assert!(AptConfig::from("Acquire::http::Proxy \"http://hoge;").is_err());
+  }
+
+  #[test]
+  fn test_read_apt_conf() {
+    // `apt.conf` overrides `apt.conf.d`.
+    let config = AptConfig::read(Path::new("./tests/resources/apt")).unwrap();
+    assert_eq!(config.get("Acquire::Retries"), Some("3"));
+    assert_eq!(
+      config.get("Acquire::http::Proxy"),
+      Some("http://proxy.example.com:3128")
+    );
+    assert_eq!(config.get("Acquire::http::Timeout"), Some("60"));
+
+    let config = AptConfig::read(Path::new("./tests/resources/not-exist")).unwrap();
+    assert_eq!(config, AptConfig::default());
+  }
+}
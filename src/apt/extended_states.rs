@@ -3,6 +3,7 @@
  Refer to `/docs/extended_states.md` for the details.
 */
 
+use crate::dpkg::arch::native_arch;
 use crate::package::error::PackageError;
 use crate::package::package::Package;
 
@@ -45,13 +46,23 @@ impl AptExtendedStateClient {
   }
 
   // Update entry of apt extended_states.
-  // If an entry for `package_name` exists, update its value or remove the entry.
+  // If an entry for `package_name` of `arch` exists, update its value or remove the entry.
+  // Empty and `all` architectures are recorded as native one.
   // NOTE: if `auto_installed` is false, it just removes the entry.
-  pub fn update(&self, package_name: &str, auto_installed: bool) -> Result<(), PackageError> {
+  pub fn update(
+    &self,
+    package_name: &str,
+    arch: &str,
+    auto_installed: bool,
+  ) -> Result<(), PackageError> {
+    let arch = match arch {
+      "" | "all" => native_arch(),
+      arch => arch,
+    };
     let mut extended_infos = self.read()?;
     let target_info_ix = extended_infos
       .iter()
-      .position(|info| info.name == package_name);
+      .position(|info| info.name == package_name && info.arch == arch);
     let new_extended_file_str = match target_info_ix {
       Some(target_info_ix) => {
         if auto_installed {
@@ -65,7 +76,7 @@ impl AptExtendedStateClient {
         if auto_installed {
           let target_info = AptExtendedPackageInfo {
             name: package_name.into(),
-            arch: "amd64".into(),
+            arch: arch.into(),
             automatic_installed: auto_installed,
           };
           extended_infos.push(target_info);
@@ -146,3 +157,44 @@ fn extended_states_to_string(infos: &Vec<AptExtendedPackageInfo>) -> String {
     .join("\n\n")
     + "\n"
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_update_per_arch() {
+    let path = std::env::temp_dir().join(format!("rapt2-extended-{}", std::process::id()));
+    fs::write(&path, "").unwrap();
+    let client = AptExtendedStateClient::new(&path);
+    let autos = |client: &AptExtendedStateClient| -> Vec<(String, String)> {
+      let mut autos: Vec<(String, String)> = client
+        .read()
+        .unwrap()
+        .into_iter()
+        .map(|info| (info.name, info.arch))
+        .collect();
+      autos.sort();
+      autos
+    };
+
+    // foreign and native packages of the same name have their own records.
+    client.update("libc6", "i386", true).unwrap();
+    client.update("libc6", "", true).unwrap();
+    let mut expected = vec![
+      ("libc6".to_string(), "i386".to_string()),
+      ("libc6".to_string(), native_arch().to_string()),
+    ];
+    expected.sort();
+    assert_eq!(autos(&client), expected);
+
+    // marking foreign one as manual keeps native one automatic.
+    client.update("libc6", "i386", false).unwrap();
+    assert_eq!(
+      autos(&client),
+      vec![("libc6".to_string(), native_arch().to_string())]
+    );
+
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,612 @@
+/*
+ This file defines client for IO of `/etc/apt/preferences` and files in `/etc/apt/preferences.d`,
+ and the policy to select a candidate version of packages based on them.
+ Refer to apt_preferences(5) for the details.
+
+ Priority of each version is decided as below:
+   - the first matching pin specific to the package (`Package: <name>`)
+   - the first matching general pin (`Package: *`)
+   - 500 for available versions, and 100 for the installed version
+ The candidate is the version with the highest priority (higher version wins in a tie).
+ Versions with negative priority are never selected.
+ Versions older than the installed one are selected only if its priority is 1000 or higher.
+*/
+
+use crate::package::{client::PackageWithSource, package::Package};
+use crate::source::{release::Release, source::Source};
+use crate::util::split_by_empty_line;
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub const DEFAULT_PRIORITY: i32 = 500;
+pub const INSTALLED_PRIORITY: i32 = 100;
+pub const DOWNGRADE_PRIORITY: i32 = 1000; // minimum priority to allow downgrade
+
+#[derive(Error, Debug)]
+pub enum PreferenceError {
+  #[error("error in file IO")]
+  FileIoError(#[from] io::Error),
+
+  #[error("invalid preferences format in {file:?}: {msg:?}")]
+  InvalidFormat { file: String, msg: String },
+
+  #[error("invalid field in preferences {file:?}: {field:?} = {value:?}")]
+  InvalidField {
+    file: String,
+    field: String,
+    value: String,
+  },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Pin {
+  Release(Vec<(String, String)>), // conditions such as `a=focal-backports`
+  Origin(String),                 // hostname of the repository
+  Version(glob::Pattern),         // glob pattern of version
+}
+
+impl Pin {
+  fn from(s: &str) -> Result<Self, ()> {
+    let (kind, value) = match s.trim().split_once(' ') {
+      Some((kind, value)) => (kind, value.trim()),
+      None => (s.trim(), ""),
+    };
+    match kind {
+      "release" => {
+        let mut conditions = vec![];
+        for condition in value.split(',') {
+          let condition = condition.trim();
+          if condition.is_empty() {
+            continue;
+          }
+          match condition.split_once('=') {
+            Some((key, value)) => conditions.push((key.trim().into(), value.trim().into())),
+            // bare value means archive name.
+            None => conditions.push(("a".into(), condition.into())),
+          }
+        }
+        Ok(Self::Release(conditions))
+      }
+      "origin" => Ok(Self::Origin(value.trim_matches('"').into())),
+      "version" => match glob::Pattern::new(value) {
+        Ok(pattern) => Ok(Self::Version(pattern)),
+        Err(_) => Err(()),
+      },
+      _ => Err(()),
+    }
+  }
+}
+
+impl std::fmt::Display for Pin {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Release(conditions) => {
+        let conditions: Vec<String> = conditions
+          .iter()
+          .map(|(key, value)| format!("{}={}", key, value))
+          .collect();
+        write!(f, "release {}", conditions.join(","))
+      }
+      Self::Origin(origin) => write!(f, "origin \"{}\"", origin),
+      Self::Version(version) => write!(f, "version {}", version),
+    }
+  }
+}
+
+// pattern of package names, which is compiled once when preferences are parsed.
+#[derive(Debug, Clone)]
+pub enum PackagePattern {
+  Glob(glob::Pattern), // name or glob pattern
+  Regex(Regex),        // regex surrounded by '/'
+}
+
+impl PackagePattern {
+  fn from(s: &str) -> Result<Self, ()> {
+    if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+      match Regex::new(&s[1..s.len() - 1]) {
+        Ok(regex) => Ok(Self::Regex(regex)),
+        Err(_) => Err(()),
+      }
+    } else {
+      match glob::Pattern::new(s) {
+        Ok(pattern) => Ok(Self::Glob(pattern)),
+        Err(_) => Err(()),
+      }
+    }
+  }
+
+  pub fn matches(&self, name: &str) -> bool {
+    match self {
+      Self::Glob(pattern) => pattern.matches(name),
+      Self::Regex(regex) => regex.is_match(name),
+    }
+  }
+}
+
+// compare as written in preferences, cuz `Regex` can't be compared.
+impl PartialEq for PackagePattern {
+  fn eq(&self, other: &Self) -> bool {
+    self.to_string() == other.to_string()
+  }
+}
+
+impl Eq for PackagePattern {}
+
+impl std::fmt::Display for PackagePattern {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Glob(pattern) => write!(f, "{}", pattern),
+      Self::Regex(regex) => write!(f, "/{}/", regex),
+    }
+  }
+}
+
+// a stanza of preferences file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Preference {
+  pub packages: Vec<PackagePattern>,
+  pub pin: Pin,
+  pub priority: i32,
+  pub file: PathBuf, // file which defines this preference
+}
+
+impl Preference {
+  // `Package: *` is general, and the others are specific to packages.
+  pub fn is_general(&self) -> bool {
+    matches!(&self.packages[..], [PackagePattern::Glob(pattern)] if pattern.as_str() == "*")
+  }
+
+  pub fn matches_package(&self, name: &str) -> bool {
+    self.packages.iter().any(|pattern| pattern.matches(name))
+  }
+
+  // check if this preference applies to the version of `package` in `source`.
+  // `source` is `None` for the installed version.
+  pub fn matches(
+    &self,
+    package: &Package,
+    source: Option<&Source>,
+    release: Option<&Release>,
+  ) -> bool {
+    if !self.matches_package(&package.name) {
+      return false;
+    }
+    match &self.pin {
+      Pin::Version(pattern) => {
+        pattern.matches(&package.version.to_raw_string())
+          || pattern.matches(&package.version.to_string())
+      }
+      Pin::Origin(origin) => match source {
+        Some(source) => source_host(source) == origin,
+        None => false,
+      },
+      Pin::Release(conditions) => match source {
+        Some(source) => conditions
+          .iter()
+          .all(|(key, value)| release_matches(key, value, source, release)),
+        None => false,
+      },
+    }
+  }
+}
+
+fn source_host(source: &Source) -> &str {
+  let url = match source.url.split_once("://") {
+    Some((_, rest)) => rest,
+    None => &source.url,
+  };
+  url.split('/').next().unwrap_or("")
+}
+
+fn release_matches(key: &str, value: &str, source: &Source, release: Option<&Release>) -> bool {
+  let distro = source.distro.trim_end_matches('/');
+  let field = |f: fn(&Release) -> &Option<String>| release.and_then(|release| f(release).clone());
+  match key {
+    "a" => value == distro || field(|r| &r.suite).as_deref() == Some(value),
+    "n" => value == distro || field(|r| &r.codename).as_deref() == Some(value),
+    "c" => value == source.component.to_string(),
+    "o" => field(|r| &r.origin).as_deref() == Some(value),
+    "l" => field(|r| &r.label).as_deref() == Some(value),
+    "v" => field(|r| &r.version).as_deref() == Some(value),
+    _ => false,
+  }
+}
+
+// parse content of a preferences file.
+pub fn parse_preferences(content: &str, file: &Path) -> Result<Vec<Preference>, PreferenceError> {
+  let mut results = vec![];
+  let filename = file.to_string_lossy().to_string();
+
+  // remove comments first cuz it can separate stanzas.
+  let content: Vec<&str> = content
+    .lines()
+    .filter(|line| !line.trim_start().starts_with('#'))
+    .collect();
+  for block in split_by_empty_line(&content.join("\n")) {
+    let mut packages = None;
+    let mut pin = None;
+    let mut priority = None;
+    for line in &block {
+      let (field, value) = match line.split_once(':') {
+        Some((field, value)) => (field.trim(), value.trim()),
+        None => {
+          return Err(PreferenceError::InvalidFormat {
+            file: filename,
+            msg: line.clone(),
+          })
+        }
+      };
+      match field.to_lowercase().as_str() {
+        "package" => match value.split_whitespace().map(PackagePattern::from).collect() {
+          Ok(patterns) => packages = Some(patterns),
+          Err(()) => {
+            return Err(PreferenceError::InvalidField {
+              file: filename,
+              field: field.into(),
+              value: value.into(),
+            })
+          }
+        },
+        "pin" => match Pin::from(value) {
+          Ok(p) => pin = Some(p),
+          Err(()) => {
+            return Err(PreferenceError::InvalidField {
+              file: filename,
+              field: field.into(),
+              value: value.into(),
+            })
+          }
+        },
+        "pin-priority" => match value.parse::<i32>() {
+          Ok(p) => priority = Some(p),
+          Err(_) => {
+            return Err(PreferenceError::InvalidField {
+              file: filename,
+              field: field.into(),
+              value: value.into(),
+            })
+          }
+        },
+        _ => continue, // such as `Explanation`
+      }
+    }
+
+    match (packages, pin, priority) {
+      (Some(packages), Some(pin), Some(priority)) => results.push(Preference {
+        packages,
+        pin,
+        priority,
+        file: file.to_path_buf(),
+      }),
+      _ => {
+        return Err(PreferenceError::InvalidFormat {
+          file: filename,
+          msg: "stanza lacks `Package`, `Pin` or `Pin-Priority`.".into(),
+        })
+      }
+    }
+  }
+
+  Ok(results)
+}
+
+pub struct AptPreferencesClient {
+  source_dir: PathBuf, // apt base dir, such as `/etc/apt`
+}
+
+impl AptPreferencesClient {
+  pub fn new(source_dir: &Path) -> Self {
+    Self {
+      source_dir: source_dir.to_path_buf(),
+    }
+  }
+
+  // read `preferences` and `preferences.d/*` in this order.
+  pub fn read_all(&self) -> Result<Vec<Preference>, PreferenceError> {
+    let mut results = vec![];
+    for path in self.find_candidates() {
+      let content = fs::read_to_string(&path)?;
+      results.extend(parse_preferences(&content, &path)?);
+    }
+
+    Ok(results)
+  }
+
+  // find and return candidate preferences files.
+  fn find_candidates(&self) -> Vec<PathBuf> {
+    let mut target_pathes = vec![];
+
+    let preferences_path = self.source_dir.join("preferences");
+    if preferences_path.is_file() {
+      target_pathes.push(preferences_path);
+    }
+
+    // files in `preferences.d` are read in alphabetical order.
+    // only files without extension or with `.pref` are valid as apt does.
+    let preferences_d_path = self.source_dir.join("preferences.d");
+    if let Ok(candidates) = fs::read_dir(preferences_d_path) {
+      let mut pathes: Vec<PathBuf> = candidates
+        .flatten()
+        .map(|ent| ent.path())
+        .filter(|path| {
+          path.is_file()
+            && match path.extension() {
+              Some(ext) => ext == "pref",
+              None => true,
+            }
+        })
+        .collect();
+      pathes.sort();
+      target_pathes.extend(pathes);
+    }
+
+    target_pathes
+  }
+}
+
+#[derive(Debug, Default)]
+pub struct CandidatePolicy {
+  preferences: Vec<Preference>,
+  releases: HashMap<Source, Release>, // cached Release of each source
+}
+
+impl CandidatePolicy {
+  // `list_dir` is searched for cached InRelease of `sources`.
+  pub fn new(preferences: Vec<Preference>, sources: &[Source], list_dir: &Path) -> Self {
+    let mut releases = HashMap::new();
+    for source in sources {
+      if let Some(release) = Release::read(&list_dir.join(source.inrelease_filename())) {
+        releases.insert(source.clone(), release);
+      }
+    }
+
+    Self {
+      preferences,
+      releases,
+    }
+  }
+
+  // read preferences in `source_dir` and construct the policy.
+  pub fn read(
+    source_dir: &Path,
+    sources: &[Source],
+    list_dir: &Path,
+  ) -> Result<Self, PreferenceError> {
+    let preferences = AptPreferencesClient::new(source_dir).read_all()?;
+    Ok(Self::new(preferences, sources, list_dir))
+  }
+
+  // preference which decides priority of `package` in `source`.
+  // `source` is `None` for the installed version.
+  pub fn matched_preference(
+    &self,
+    package: &Package,
+    source: Option<&Source>,
+  ) -> Option<&Preference> {
+    let release = source.and_then(|source| self.releases.get(source));
+    let mut matcheds = self
+      .preferences
+      .iter()
+      .filter(|preference| preference.matches(package, source, release));
+    let specific = matcheds.clone().find(|preference| !preference.is_general());
+    specific.or_else(|| matcheds.find(|preference| preference.is_general()))
+  }
+
+  pub fn priority(&self, pws: &PackageWithSource) -> i32 {
+    match self.matched_preference(&pws.package, Some(&pws.source)) {
+      Some(preference) => preference.priority,
+      None => DEFAULT_PRIORITY,
+    }
+  }
+
+  pub fn installed_priority(&self, installed: &Package) -> i32 {
+    match self.matched_preference(installed, None) {
+      Some(preference) => preference.priority,
+      None => INSTALLED_PRIORITY,
+    }
+  }
+
+  // sort `versions` in the order of preference, and remove versions never to be selected.
+  // If no version wins over `installed`, the installed version comes first to be kept.
+  pub fn order(&self, versions: &mut Vec<PackageWithSource>, installed: Option<&Package>) {
+    // priority is computed only once for each version.
+    let mut prioritized: Vec<(i32, PackageWithSource)> = versions
+      .drain(..)
+      .map(|pws| (self.priority(&pws), pws))
+      .filter(|(priority, _)| *priority >= 0)
+      .collect();
+    prioritized.sort_by(|(priority_a, a), (priority_b, b)| {
+      priority_b
+        .cmp(priority_a)
+        .then(b.package.version.partial_cmp(&a.package.version).unwrap())
+    });
+    versions.extend(prioritized.into_iter().map(|(_, pws)| pws));
+
+    if let (Some(best), Some(installed)) = (versions.first(), installed) {
+      if best.package.version != installed.version && !self.wins_over_installed(best, installed) {
+        if let Some(ix) = versions
+          .iter()
+          .position(|pws| pws.package.version == installed.version)
+        {
+          let kept = versions.remove(ix);
+          versions.insert(0, kept);
+        }
+      }
+    }
+  }
+
+  // check if `pws` is selected instead of `installed`.
+  // Versions older than the installed one win only if its priority is high enough to downgrade.
+  pub fn wins_over_installed(&self, pws: &PackageWithSource, installed: &Package) -> bool {
+    let priority = self.priority(pws);
+    priority >= self.installed_priority(installed)
+      && (pws.package.version >= installed.version || priority >= DOWNGRADE_PRIORITY)
+  }
+
+  // select the candidate version from `versions`.
+  // Returns `None` if no version is available or the installed version should be kept.
+  pub fn candidate<'a>(
+    &self,
+    versions: &'a [PackageWithSource],
+    installed: Option<&Package>,
+  ) -> Option<&'a PackageWithSource> {
+    let mut ordered = versions.to_vec();
+    self.order(&mut ordered, installed);
+    let best = ordered.first()?;
+    if let Some(installed) = installed {
+      if best.package.version != installed.version && !self.wins_over_installed(best, installed) {
+        return None;
+      }
+    }
+
+    versions
+      .iter()
+      .find(|pws| pws.package.version == best.package.version && pws.source == best.source)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::package::version::Version;
+  use crate::source::source::{ArchivedType, Component};
+
+  fn to_pws(name: &str, version: &str, distro: &str) -> PackageWithSource {
+    PackageWithSource {
+      package: Package {
+        name: name.into(),
+        version: Version::from(version).unwrap(),
+        ..Default::default()
+      },
+      source: Source {
+        archive_type: ArchivedType::DEB,
+        url: "http://archive.ubuntu.com/ubuntu".into(),
+        distro: distro.into(),
+        component: Component::MAIN,
+        options: Default::default(),
+        mirrors: vec![],
+      },
+      dpkg_status: None,
+    }
+  }
+
+  #[test]
+  fn test_parse_preferences() {
+    let content = "
+# comment
+Package: *
+Pin: release a=focal-backports
+Pin-Priority: 100
+
+Explanation: prefer nano
+Package: nano /^vim/
+Pin: version 4.*
+Pin-Priority: 990
+
+Package: jq
+Pin: origin \"archive.ubuntu.com\"
+Pin-Priority: -1
+";
+    let preferences = parse_preferences(content, Path::new("preferences")).unwrap();
+    assert_eq!(preferences.len(), 3);
+    assert!(preferences[0].is_general());
+    assert_eq!(
+      preferences[0].pin,
+      Pin::Release(vec![("a".into(), "focal-backports".into())])
+    );
+    let packages: Vec<String> = preferences[1]
+      .packages
+      .iter()
+      .map(|pattern| pattern.to_string())
+      .collect();
+    assert_eq!(packages, vec!["nano", "/^vim/"]);
+    assert!(preferences[1].matches_package("vim-tiny"));
+    assert_eq!(preferences[1].priority, 990);
+    assert_eq!(preferences[2].pin, Pin::Origin("archive.ubuntu.com".into()));
+
+    assert!(parse_preferences("Package: *\nPin-Priority: 1", Path::new("p")).is_err());
+    // invalid patterns are rejected when parsed.
+    assert!(parse_preferences(
+      "Package: /(/\nPin: release a=focal\nPin-Priority: 1",
+      Path::new("p")
+    )
+    .is_err());
+    assert!(parse_preferences(
+      "Package: *\nPin: version [\nPin-Priority: 1",
+      Path::new("p")
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn test_candidate_policy() {
+    let preferences = parse_preferences(
+      "
+Package: *
+Pin: release a=focal-backports
+Pin-Priority: 100
+
+Package: jq
+Pin: release n=focal-backports
+Pin-Priority: 600
+
+Package: gawk
+Pin: version 4.*
+Pin-Priority: 1001
+",
+      Path::new("preferences"),
+    )
+    .unwrap();
+    let policy = CandidatePolicy {
+      preferences,
+      releases: HashMap::new(),
+    };
+
+    // backports are not preferred by default.
+    let versions = vec![
+      to_pws("nano", "5.0-1", "focal-backports"),
+      to_pws("nano", "4.8-1", "focal"),
+    ];
+    let candidate = policy.candidate(&versions, None).unwrap();
+    assert_eq!(candidate.package.version, Version::from("4.8-1").unwrap());
+
+    // specific pin wins over general one.
+    let versions = vec![
+      to_pws("jq", "1.6-1", "focal"),
+      to_pws("jq", "1.7-1", "focal-backports"),
+    ];
+    let candidate = policy.candidate(&versions, None).unwrap();
+    assert_eq!(candidate.package.version, Version::from("1.7-1").unwrap());
+
+    // installed version is kept unless priority is high enough to downgrade.
+    let installed = to_pws("nano", "4.9-1", "focal").package;
+    let versions = vec![to_pws("nano", "4.8-1", "focal")];
+    assert!(policy.candidate(&versions, Some(&installed)).is_none());
+    let installed = to_pws("gawk", "5.0-1", "focal").package;
+    let versions = vec![to_pws("gawk", "4.2-1", "focal")];
+    let candidate = policy.candidate(&versions, Some(&installed)).unwrap();
+    assert_eq!(candidate.package.version, Version::from("4.2-1").unwrap());
+
+    // `order()` agrees with `candidate()`, so the installed version comes first if kept.
+    let installed = to_pws("nano", "4.9-1", "focal").package;
+    let mut versions = vec![
+      to_pws("nano", "4.8-1", "focal"),
+      to_pws("nano", "4.9-1", "focal"),
+      to_pws("nano", "5.0-1", "focal-backports"),
+    ];
+    policy.order(&mut versions, Some(&installed));
+    assert_eq!(versions[0].package.version, installed.version);
+    let installed = to_pws("gawk", "5.0-1", "focal").package;
+    let mut versions = vec![
+      to_pws("gawk", "5.0-1", "focal"),
+      to_pws("gawk", "4.2-1", "focal"),
+    ];
+    policy.order(&mut versions, Some(&installed));
+    assert_eq!(versions[0].package.version, Version::from("4.2-1").unwrap());
+  }
+}
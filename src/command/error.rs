@@ -18,15 +18,27 @@ pub enum RaptError {
   #[error("Package related error")]
   RaptPackageError(#[from] PackageError),
 
+  #[error("Preferences related error")]
+  RaptPreferenceError(#[from] crate::apt::preferences::PreferenceError),
+
+  #[error("apt.conf related error")]
+  RaptConfigError(#[from] crate::apt::config::ConfigError),
+
   #[error("Permission related error.")]
   PermissionDenied,
 
   #[error("Invalid input: {msg:?}")]
   InvalidInput { msg: String },
 
+  #[error("Package not found: {name:?}")]
+  PackageNotFound { name: String },
+
   #[error("Error while resolving dependency.")]
   ImpossibleDependency(#[from] crate::algorithm::dag::DagError),
 
+  #[error("Error while resolving conflicts.")]
+  UnresolvableConflict(#[from] crate::algorithm::conflict::ConflictError),
+
   #[error("Unknown command: {command:?}")]
   UnknownCommand { command: SubCommand },
 
@@ -3,7 +3,8 @@
  `Rapt` holds context information and executes subcommands.
 */
 
-use super::{error::RaptError, subcommand::*};
+use super::error::RaptError;
+use super::subcommand::*;
 use crate::{context::Context, util::emoji::EMOJI_CROSS};
 
 use console::style;
@@ -22,6 +23,9 @@ impl Rapt {
     let result = match &self.command {
       SubCommand::UPDATE { args } => update::execute(&self.context, args),
       SubCommand::LIST { args } => list::execute(&self.context, args),
+      SubCommand::SEARCH { args } => search::execute(&self.context, args),
+      SubCommand::SHOW { args } => show::execute(&self.context, args),
+      SubCommand::POLICY { args } => policy::execute(&self.context, args),
       SubCommand::DEP { args } => dep::execute(&self.context, args),
       SubCommand::INSTALL { args } => install::execute(&self.context, args),
       SubCommand::UPGRADE { args } => upgrade::execute(&self.context, args),
@@ -29,19 +33,21 @@ impl Rapt {
       SubCommand::REMOVE { args } => remove::execute(&self.context, args),
       SubCommand::AUTOREMOVE { args } => autoremove::execute(&self.context, args),
       SubCommand::PURGE { args } => purge::execute(&self.context, args),
-      _ => Err(RaptError::UnknownCommand {
-        command: self.command.clone(),
-      }),
     };
 
     if let Err(err) = result {
-      println!(
-        "{} {}: rapt2 aborted an operation due to below error:",
-        EMOJI_CROSS,
-        style("Error").red().bold()
-      );
-      println!("{}", err.to_string());
-      std::process::exit(1);
+      abort(&err);
     }
   }
 }
+
+// show `err` and exit.
+pub fn abort(err: &RaptError) -> ! {
+  println!(
+    "{} {}: rapt2 aborted an operation due to below error:",
+    EMOJI_CROSS,
+    style("Error").red().bold()
+  );
+  println!("{}", err.to_string());
+  std::process::exit(1);
+}
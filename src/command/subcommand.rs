@@ -5,8 +5,11 @@ pub mod clean;
 pub mod dep;
 pub mod install;
 pub mod list;
+pub mod policy;
 pub mod purge;
 pub mod remove;
+pub mod search;
+pub mod show;
 pub mod update;
 pub mod upgrade;
 
@@ -42,10 +45,21 @@ pub enum SubCommand {
     #[clap(flatten)]
     args: PurgeArgs,
   },
-  #[clap(about = "(not implemented)")]
-  SEARCH,
-  #[clap(about = "(not implemented)")]
-  SHOW,
+  #[clap(about = "Search packages by regex over names and descriptions.")]
+  SEARCH {
+    #[clap(flatten)]
+    args: SearchArgs,
+  },
+  #[clap(about = "Show detailed information of a package.")]
+  SHOW {
+    #[clap(flatten)]
+    args: ShowArgs,
+  },
+  #[clap(about = "Explain which version of a package is the candidate.")]
+  POLICY {
+    #[clap(flatten)]
+    args: PolicyArgs,
+  },
   #[clap(about = "List packages by name.")]
   LIST {
     #[clap(flatten)]
@@ -79,18 +93,74 @@ pub struct ListArgs {
 }
 
 #[derive(Args, Debug, Clone)]
-pub struct DepArgs {
+pub struct SearchArgs {
+  #[clap(long, help = "match only against package names.")]
+  pub names_only: bool,
+
+  #[clap(long, help = "show only installed packages.")]
+  pub installed: bool,
+
+  #[clap(
+    long,
+    help = "show only packages from given distribution. eg) focal-backports"
+  )]
+  pub distro: Option<String>,
+
+  #[clap(help = "Regex to search for.")]
+  pub keyword: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ShowArgs {
+  #[clap(
+    help = "Target package name. Architecture, version or release can be specified as `<name>:<arch>`, `<name>=<version>` or `<name>/<release>`."
+  )]
+  pub keyword: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PolicyArgs {
   #[clap(help = "Target package name.")]
   pub keyword: String,
 }
 
 #[derive(Args, Debug, Clone)]
-pub struct InstallArgs {
+pub struct DepArgs {
   #[clap(help = "Target package name.")]
   pub keyword: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct InstallArgs {
+  #[clap(
+    required = true,
+    help = "Target package names. Architecture can be given as `<name>:<arch>`, and version or release can be pinned as `<name>=<version>` or `<name>/<release>`."
+  )]
+  pub keywords: Vec<String>,
 
   #[clap(short = 'N', long, help = "Dry run.")]
   pub dry_run: bool,
+
+  #[clap(
+    long,
+    overrides_with = "no-install-recommends",
+    help = "Install recommended packages (default)."
+  )]
+  pub install_recommends: bool,
+
+  #[clap(
+    long,
+    overrides_with = "install-recommends",
+    help = "Don't install recommended packages."
+  )]
+  pub no_install_recommends: bool,
+}
+
+impl InstallArgs {
+  // recommended packages are installed unless explicitly disabled.
+  pub fn should_install_recommends(&self) -> bool {
+    !self.no_install_recommends
+  }
 }
 
 #[derive(Args, Debug, Clone)]
@@ -8,7 +8,7 @@ use crate::{
   package::{
     client::{PackageClient, PackageWithSource},
     error::PackageError,
-    package::Package,
+    package::{DependsAnyOf, Package},
   },
   source::{client::SourceClient, source::Source},
   util::emoji::*,
@@ -21,7 +21,7 @@ pub fn execute(context: &Context, args: &DepArgs) -> Result<(), RaptError> {
   let keyword = args.keyword.clone();
   // get sources
   let source_client = SourceClient::new(PathBuf::from(&context.source_dir))?;
-  let sources = source_client.read_all()?;
+  let sources = source_client.read_all_for(&context.architectures())?;
 
   // get dependencies
   println!(
@@ -34,6 +34,7 @@ pub fn execute(context: &Context, args: &DepArgs) -> Result<(), RaptError> {
     &keyword,
     &sources.into_iter().collect(),
     true,
+    false,
     None,
   ) {
     Ok(deps) => deps,
@@ -64,11 +65,14 @@ pub fn execute(context: &Context, args: &DepArgs) -> Result<(), RaptError> {
 
   let mut shown_packages = vec![];
   for root_depany in &root.package.depends {
-    let root_dep = &root_depany.depends[0];
-    if root_dep.package == keyword {
+    let root_dep = match find_chosen(root_depany, &deps) {
+      Some(root_dep) => root_dep,
+      None => continue,
+    };
+    if root_dep.package.name == keyword {
       continue;
     }
-    recursive_depends_show(&root_dep.package, &deps, 1, &mut shown_packages);
+    recursive_depends_show(&root_dep.package.name, &deps, 1, &mut shown_packages);
   }
 
   Ok(())
@@ -92,10 +96,23 @@ fn recursive_depends_show(
   // more recursive printing
   acc.push(target.package.name.clone());
   for dep_anyof in &target.package.depends {
-    recursive_depends_show(&dep_anyof.depends[0].package, packages, hierarchy + 1, acc);
+    if let Some(dep) = find_chosen(dep_anyof, packages) {
+      recursive_depends_show(&dep.package.name, packages, hierarchy + 1, acc);
+    }
   }
 }
 
+// find the alternative chosen by the resolver.
+fn find_chosen<'a>(
+  anyof: &DependsAnyOf,
+  packages: &'a HashMap<String, PackageWithSource>,
+) -> Option<&'a PackageWithSource> {
+  anyof
+    .depends
+    .iter()
+    .find_map(|dep| packages.values().find(|pws| dep.targets(&pws.package)))
+}
+
 fn show_single_package(package: &Package, source: &Source) {
   print!(
     "{} / {} {} {} -> ",
@@ -109,19 +126,26 @@ fn show_single_package(package: &Package, source: &Source) {
     .depends
     .iter()
     .map(|anyof| {
-      if let Some(version) = &anyof.depends[0].version {
-        format!(
-          "{} ({})",
-          style(&anyof.depends[0].package).yellow().dim(),
-          style(version.to_string()).dim()
-        )
-      } else {
-        format!(
-          "{} ({})",
-          style(&anyof.depends[0].package).yellow().dim(),
-          style("any").dim()
-        )
-      }
+      anyof
+        .depends
+        .iter()
+        .map(|dep| {
+          if let Some(version) = &dep.version {
+            format!(
+              "{} ({})",
+              style(&dep.package).yellow().dim(),
+              style(version.to_string()).dim()
+            )
+          } else {
+            format!(
+              "{} ({})",
+              style(&dep.package).yellow().dim(),
+              style("any").dim()
+            )
+          }
+        })
+        .collect::<Vec<String>>()
+        .join(" | ")
     })
     .collect::<Vec<String>>()
     .join(", ");
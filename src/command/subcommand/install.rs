@@ -1,23 +1,41 @@
 use super::{super::error::RaptError, InstallArgs};
 use crate::{
-  algorithm::dag::*,
+  algorithm::{conflict::plan_conflicts, dag::*},
+  apt::preferences::CandidatePolicy,
   context::Context,
   dpkg::{
     client::{DpkgClient, StatusComp},
     installer::DpkgInstaller,
+    status::DpkgStatusStatus,
   },
   net::binary::BinaryDownloader,
-  package::client::{PackageClient, PackageWithSource},
-  source::client::SourceClient,
+  package::{
+    client::{PackageClient, PackageWithSource},
+    package::{Package, PackageSpec},
+  },
+  source::{client::SourceClient, source::Source},
   util::{emoji::*, *},
 };
 
 use console::style;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 pub fn execute(context: &Context, args: &InstallArgs) -> Result<(), RaptError> {
-  let keyword = args.keyword.clone();
+  let keywords = args.keywords.clone();
+  // parse `<name>[:<arch>][=<version>|/<release>]`
+  let mut specs = vec![];
+  for keyword in &keywords {
+    match PackageSpec::from(keyword) {
+      Ok(spec) => specs.push(spec),
+      Err(()) => {
+        return Err(RaptError::InvalidInput {
+          msg: keyword.clone(),
+        })
+      }
+    }
+  }
 
   // first, search dependencies
   println!(
@@ -26,32 +44,72 @@ pub fn execute(context: &Context, args: &InstallArgs) -> Result<(), RaptError> {
     EMOJI_BOOKS
   );
   let source_client = SourceClient::new(PathBuf::from(&context.source_dir))?;
-  let sources = source_client.read_all()?;
+  let sources: Vec<Source> = source_client
+    .read_all_for(&context.architectures())?
+    .into_iter()
+    .collect();
   let mut package_client = PackageClient::new(PathBuf::from(&context.list_dir))?;
+  package_client.set_policy(CandidatePolicy::read(
+    &context.source_dir,
+    &sources,
+    &context.list_dir,
+  )?);
   let mut dpkg_client = DpkgClient::new(
     PathBuf::from(&context.dpkg_dir),
     context.extended_state.clone(),
   );
-  let deps = package_client.get_package_with_deps(
-    &keyword,
-    &sources.into_iter().collect(),
+  let deps = package_client.get_packages_with_deps(
+    &keywords,
+    &sources,
     false,
+    args.should_install_recommends(),
     Some(&mut dpkg_client),
   )?;
   if deps.is_empty() {
     println!(
       "{} package {} is already up-to-date.",
       EMOJI_SPARKLES,
-      style(keyword).cyan()
+      style(keywords.join(", ")).cyan()
     );
     return Ok(());
   }
 
-  let sorted_deps: Vec<PackageWithSource> = sort_depends(deps, &keyword)?;
+  // already up-to-date targets are omitted from the transaction.
+  let (targets, uptodates): (Vec<String>, Vec<String>) = specs
+    .into_iter()
+    .map(|spec| spec.full_name())
+    .partition(|name| deps.iter().any(|pws| &pws.package.full_name() == name));
+  for uptodate in uptodates {
+    println!(
+      "{} package {} is already up-to-date.",
+      EMOJI_SPARKLES,
+      style(uptodate).cyan()
+    );
+  }
+  let sorted_deps: Vec<PackageWithSource> = sort_depends_multi(deps, &targets)?;
   let layers = split_layers(&sorted_deps);
 
+  // plan removals and deconfigurations before touching dpkg.
+  let conflict_plan = plan_conflicts(&sorted_deps, &dpkg_client.get_installed_packages()?)?;
+
   // show info of packages
-  show_to_install_packages(&sorted_deps, &keyword);
+  show_to_install_packages(&sorted_deps, &targets);
+  show_conflict_plan(&conflict_plan);
+  show_suggested_packages(&sorted_deps, &dpkg_client.get_installed_packages()?);
+
+  // warn downgrades clearly before running dpkg.
+  let downgrade_num = sorted_deps
+    .iter()
+    .filter(|pws| matches!(&pws.dpkg_status, Some(StatusComp::NEWER(_))))
+    .count();
+  if downgrade_num != 0 {
+    println!(
+      "{} {} {} package(s) would be downgraded. Downgrades are not tested by maintainers.",
+      EMOJI_EXC,
+      style("WARNING:").red().bold(),
+      style(downgrade_num).bold().cyan(),
+    );
+  }
 
   // if verbose mode, show dependencies.
   if context.verbose {
@@ -78,20 +136,12 @@ pub fn execute(context: &Context, args: &InstallArgs) -> Result<(), RaptError> {
     style("[2/3]").bold().dim(),
     EMOJI_EARTH,
   );
-  let progress = default_progbar(sorted_deps.len() as u64);
-
+  if context.verbose {
+    show_net_config_verbose(context.net_config()?);
+  }
   let binary_client =
     BinaryDownloader::new(sorted_deps.clone(), PathBuf::from(&context.archive_dir))?;
-  for downloader in binary_client.into_iter() {
-    progress.set_message(
-      style(downloader.pws.package.name.clone())
-        .cyan()
-        .to_string(),
-    );
-    downloader.download()?;
-    progress.inc(1);
-  }
-  progress.abandon_with_message("Complete.");
+  binary_client.download_all(&context.download_pool()?)?;
 
   // install them
   println!(
@@ -106,20 +156,31 @@ pub fn execute(context: &Context, args: &InstallArgs) -> Result<(), RaptError> {
   let progress = ProgressBar::new(sorted_deps.len() as u64 * 2);
   progress.set_style(prog_style);
 
+  // remove conflicting packages which dpkg doesn't replace by itself.
+  for removal in conflict_plan.explicit_removals() {
+    progress.println(format!(
+      "   remove    {} (conflicts with {})",
+      removal.package.name, removal.cause
+    ));
+    dpkg_client.remove_package(&removal.package, false)?;
+  }
+
   for layer in layers.into_iter().rev() {
     let dpkg_client = DpkgInstaller::new(
       PathBuf::from(&context.archive_dir),
       layer.into_iter().rev().collect(),
+      // newly installed dependencies are marked as automatically installed,
+      // and records of installed ones are kept as they are.
+      // both are keyed by qualified names not to confuse `libc6:i386` with native `libc6`.
       sorted_deps
         .iter()
-        .map(|pws| {
-          if pws.package.name == keyword {
-            "".into()
-          } else {
-            pws.package.name.to_string()
-          }
-        })
+        .filter(|pws| pws.dpkg_status == Some(StatusComp::NOTINSTALLED))
+        .map(|pws| pws.package.full_name())
+        .filter(|name| !targets.contains(name))
         .collect(),
+      // only named targets are marked as manually installed.
+      targets.clone(),
+      conflict_plan.breaking_packages(),
       context.extended_state.clone(),
     )?;
     for extracter in dpkg_client.extracters_iter() {
@@ -138,13 +199,17 @@ pub fn execute(context: &Context, args: &InstallArgs) -> Result<(), RaptError> {
   Ok(())
 }
 
-fn show_to_install_packages(pwss: &[PackageWithSource], target: &str) {
+fn show_to_install_packages(pwss: &[PackageWithSource], targets: &[String]) {
   println!(
     "Below packages are to be installed({}):",
     style(pwss.len()).bold().cyan()
   );
 
-  println!("  {} Target: {}", EMOJI_TARGET, style(target).bold());
+  println!(
+    "  {} Target: {}",
+    EMOJI_TARGET,
+    style(targets.join(", ")).bold()
+  );
 
   // show newly installed packages.
   let news: Vec<&PackageWithSource> = pwss
@@ -160,7 +225,7 @@ fn show_to_install_packages(pwss: &[PackageWithSource], target: &str) {
     let package = &new.package;
     println!(
       "\t - {} ({})",
-      style(&package.name).yellow(),
+      style(package.full_name()).yellow(),
       style(&package.version).dim()
     );
   }
@@ -181,15 +246,68 @@ fn show_to_install_packages(pwss: &[PackageWithSource], target: &str) {
     match upgrade.dpkg_status.clone().unwrap() {
       StatusComp::OLD(old_version) => println!(
         "\t - {} ({} -> {})",
-        style(&package.name).yellow(),
+        style(package.full_name()).yellow(),
         style(&old_version).dim(),
         style(&package.version).dim()
       ),
       _ => println!(
         "\t - {} (-> {})",
-        style(&package.name).yellow(),
+        style(package.full_name()).yellow(),
         style(&package.version).dim()
       ),
     }
   }
+
+  // show downgraded packages.
+  let downgrades: Vec<&PackageWithSource> = pwss
+    .iter()
+    .filter(|pws| matches!(&pws.dpkg_status, Some(StatusComp::NEWER(_))))
+    .collect();
+  if !downgrades.is_empty() {
+    println!(
+      "  {} Downgraded({}):",
+      EMOJI_DOWN,
+      style(downgrades.len()).bold().cyan()
+    );
+    for downgrade in downgrades {
+      if let Some(StatusComp::NEWER(new_version)) = &downgrade.dpkg_status {
+        println!(
+          "\t - {} ({} -> {})",
+          style(downgrade.package.full_name()).red(),
+          style(new_version).dim(),
+          style(&downgrade.package.version).dim()
+        );
+      }
+    }
+  }
+}
+
+// show suggested packages which are neither to be installed nor installed.
+fn show_suggested_packages(pwss: &[PackageWithSource], installeds: &HashSet<Package>) {
+  let mut suggests: Vec<String> = vec![];
+  for pws in pwss {
+    for anyof in &pws.package.suggests {
+      let satisfied = pwss.iter().any(|pws| anyof.targets(&pws.package))
+        || installeds.iter().any(|installed| {
+          matches!(&installed.status, Some(status) if status.status == DpkgStatusStatus::Installed)
+            && anyof.targets(installed)
+        });
+      let anyof = anyof.to_string();
+      if !satisfied && !suggests.contains(&anyof) {
+        suggests.push(anyof);
+      }
+    }
+  }
+  if suggests.is_empty() {
+    return;
+  }
+
+  println!(
+    "  {} Suggested ({}):",
+    EMOJI_INFORMATION,
+    style(suggests.len()).bold().cyan()
+  );
+  for suggest in suggests {
+    println!("\t - {}", style(suggest).dim());
+  }
 }
@@ -14,7 +14,7 @@ pub fn execute(context: &Context, args: &ListArgs) -> Result<(), RaptError> {
   let keyword = args.keyword.clone();
   // get sources
   let source_client = SourceClient::new(PathBuf::from(&context.source_dir))?;
-  let sources = source_client.read_all()?;
+  let sources = source_client.read_all_for(&context.architectures())?;
 
   // get list of packages
   println!("{} Reading package lists...", EMOJI_BOOKS,);
@@ -0,0 +1,133 @@
+/*
+ This file implements `policy` subcommand.
+*/
+
+use super::{super::error::RaptError, PolicyArgs};
+use crate::{
+  apt::preferences::CandidatePolicy,
+  context::Context,
+  dpkg::{client::DpkgClient, status::DpkgStatusStatus},
+  package::{client::PackageClient, package::Package},
+  source::{client::SourceClient, source::Source},
+  util::emoji::*,
+};
+
+use console::style;
+use std::path::PathBuf;
+
+pub fn execute(context: &Context, args: &PolicyArgs) -> Result<(), RaptError> {
+  let name = &args.keyword;
+
+  // get sources and preferences
+  let source_client = SourceClient::new(PathBuf::from(&context.source_dir))?;
+  let sources: Vec<Source> = source_client
+    .read_all_for(&context.architectures())?
+    .into_iter()
+    .collect();
+  let policy = CandidatePolicy::read(&context.source_dir, &sources, &context.list_dir)?;
+
+  // get all versions of the package
+  println!("{} Reading package lists...", EMOJI_BOOKS);
+  let mut package_client = PackageClient::new(PathBuf::from(&context.list_dir))?;
+  let versions = package_client.read_all_versions_with_source(name, &sources)?;
+
+  // get installed status
+  let mut dpkg_client = DpkgClient::new(context.dpkg_dir.clone(), context.extended_state.clone());
+  let installeds = dpkg_client.get_installed_packages()?;
+  let installed = installeds
+    .get(&Package {
+      name: name.clone(),
+      ..Default::default()
+    })
+    .filter(|installed| {
+      matches!(&installed.status, Some(status) if status.status == DpkgStatusStatus::Installed)
+    });
+
+  if versions.is_empty() && installed.is_none() {
+    return Err(RaptError::PackageNotFound { name: name.clone() });
+  }
+
+  let candidate = policy.candidate(&versions, installed);
+  println!("{}:", style(name).bold());
+  println!(
+    "  Installed: {}",
+    match installed {
+      Some(installed) => installed.version.to_string(),
+      None => "(none)".into(),
+    }
+  );
+  println!(
+    "  Candidate: {}",
+    match (candidate, installed) {
+      (Some(candidate), _) => candidate.package.version.to_string(),
+      (None, Some(installed)) => installed.version.to_string(),
+      (None, None) => "(none)".into(),
+    }
+  );
+
+  // explain why the candidate wins.
+  match candidate {
+    Some(candidate) => match policy.matched_preference(&candidate.package, Some(&candidate.source))
+    {
+      Some(preference) => println!(
+        "  {} pinned to {} by {} (Pin: {})",
+        EMOJI_INFORMATION,
+        style(preference.priority).cyan(),
+        style(preference.file.to_string_lossy()).dim(),
+        preference.pin,
+      ),
+      None => println!(
+        "  {} the most preferred version with the default priority.",
+        EMOJI_INFORMATION,
+      ),
+    },
+    None => {
+      if installed.is_some() {
+        println!(
+          "  {} the installed version is kept cuz no other version is preferred over it.",
+          EMOJI_INFORMATION,
+        );
+      }
+    }
+  }
+
+  // show version table
+  println!("  Version table:");
+  if let Some(installed) = installed {
+    if !versions
+      .iter()
+      .any(|pws| pws.package.version == installed.version)
+    {
+      println!(" {} {}", style("***").green(), installed.version);
+      println!(
+        "        {} {}",
+        policy.installed_priority(installed),
+        style("/var/lib/dpkg/status").dim()
+      );
+    }
+  }
+  for pws in &versions {
+    let is_installed = match installed {
+      Some(installed) => installed.version == pws.package.version,
+      None => false,
+    };
+    println!(
+      " {} {}",
+      if is_installed {
+        style("***").green()
+      } else {
+        style("   ")
+      },
+      pws.package.version
+    );
+    println!(
+      "        {} {} {} {}",
+      policy.priority(pws),
+      style(&pws.source.url).dim(),
+      style(&pws.source.distro).dim(),
+      style(pws.source.component.to_string()).dim(),
+    );
+  }
+
+  Ok(())
+}
@@ -0,0 +1,76 @@
+/*
+ This file implements `search` subcommand.
+*/
+
+use super::{super::error::RaptError, SearchArgs};
+use crate::{
+  context::Context,
+  dpkg::{client::DpkgClient, status::DpkgStatusStatus},
+  package::client::{PackageClient, PackageWithSource},
+  source::{client::SourceClient, source::Source},
+  util::emoji::*,
+};
+
+use console::style;
+use std::path::PathBuf;
+
+pub fn execute(context: &Context, args: &SearchArgs) -> Result<(), RaptError> {
+  let keyword = args.keyword.clone();
+  // get sources
+  let source_client = SourceClient::new(PathBuf::from(&context.source_dir))?;
+  let sources: Vec<Source> = source_client
+    .read_all_for(&context.architectures())?
+    .into_iter()
+    .filter(|source| match &args.distro {
+      Some(distro) => &source.distro == distro,
+      None => true,
+    })
+    .collect();
+
+  // search packages
+  println!("{} Reading package lists...", EMOJI_BOOKS);
+  let mut package_client = PackageClient::new(PathBuf::from(&context.list_dir))?;
+  let mut target_packages: Vec<PackageWithSource> = package_client
+    .search_by_regex_with_source(&keyword, args.names_only, sources)?
+    .into_iter()
+    .collect();
+
+  // filter out not-installed packages if needed
+  if args.installed {
+    let mut dpkg_client = DpkgClient::new(context.dpkg_dir.clone(), context.extended_state.clone());
+    let installeds = dpkg_client.get_installed_packages()?;
+    target_packages.retain(|pws| match installeds.get(&pws.package) {
+      Some(installed) => match &installed.status {
+        Some(status) => status.status == DpkgStatusStatus::Installed,
+        None => false,
+      },
+      None => false,
+    });
+  }
+
+  // show result
+  if target_packages.is_empty() {
+    println!("{} Found no package...", EMOJI_CROSS);
+    return Ok(());
+  }
+  target_packages.sort_by(|a, b| a.package.name.cmp(&b.package.name));
+  println!(
+    "{} Found {} packages:",
+    EMOJI_SPARKLES,
+    style(target_packages.len()).yellow(),
+  );
+  for pws in target_packages {
+    let package = pws.package;
+    let source = pws.source;
+    println!(
+      "\t{} / {} {} {}",
+      style(package.name).cyan(),
+      style(source.distro).dim(),
+      style(package.version).dim(),
+      style(package.arch).dim(),
+    );
+    println!("\t    {}", package.short_description);
+  }
+
+  Ok(())
+}
@@ -0,0 +1,207 @@
+/*
+ This file implements `show` subcommand.
+*/
+
+use super::{super::error::RaptError, ShowArgs};
+use crate::{
+  apt::preferences::CandidatePolicy,
+  context::Context,
+  dpkg::client::DpkgClient,
+  package::{
+    client::{select_pinned, PackageClient, PackageWithSource},
+    package::{DepType, Package, PackageSpec},
+  },
+  source::{client::SourceClient, source::Source},
+  util::emoji::*,
+};
+
+use console::style;
+use std::path::PathBuf;
+
+pub fn execute(context: &Context, args: &ShowArgs) -> Result<(), RaptError> {
+  // parse `<name>[:<arch>][=<version>|/<release>]`
+  let spec = match PackageSpec::from(&args.keyword) {
+    Ok(spec) => spec,
+    Err(()) => {
+      return Err(RaptError::InvalidInput {
+        msg: args.keyword.clone(),
+      })
+    }
+  };
+
+  // get sources
+  let source_client = SourceClient::new(PathBuf::from(&context.source_dir))?;
+  let sources: Vec<Source> = source_client
+    .read_all_for(&context.architectures())?
+    .into_iter()
+    .collect();
+
+  // get all versions of the package
+  println!("{} Reading package lists...", EMOJI_BOOKS);
+  let mut package_client = PackageClient::new(PathBuf::from(&context.list_dir))?;
+  let policy = CandidatePolicy::read(&context.source_dir, &sources, &context.list_dir)?;
+  let candidates = package_client.read_all_versions_with_source(&spec.full_name(), &sources)?;
+
+  // get installed status
+  let mut dpkg_client = DpkgClient::new(context.dpkg_dir.clone(), context.extended_state.clone());
+  let installeds = dpkg_client.get_installed_packages()?;
+  let installed = installeds.get(&Package {
+    name: spec.name.clone(),
+    arch: spec.arch.clone().unwrap_or_default(),
+    ..Default::default()
+  });
+
+  // candidate based on apt preferences is shown unless pinned.
+  let candidate = policy.candidate(&candidates, None);
+  let target = if spec.is_pinned() {
+    select_pinned(&candidates, &spec)
+  } else {
+    candidate.or_else(|| candidates.first())
+  };
+  let target = match target {
+    Some(target) => target,
+    None => {
+      return Err(RaptError::PackageNotFound {
+        name: args.keyword.clone(),
+      })
+    }
+  };
+
+  // show result
+  show_package(target, installed);
+  show_versions(&candidates, target, candidate, installed);
+
+  Ok(())
+}
+
+fn show_field(field: &str, value: &str) {
+  println!("{}: {}", style(field).bold(), value);
+}
+
+fn show_package(pws: &PackageWithSource, installed: Option<&Package>) {
+  let package = &pws.package;
+  let source = &pws.source;
+
+  println!();
+  show_field("Package", &style(&package.name).cyan().to_string());
+  show_field("Version", &package.version.to_string());
+  show_field("Architecture", &package.arch);
+  if let Some(priority) = &package.priority {
+    show_field("Priority", &priority.to_string());
+  }
+  if let Some(section) = &package.section {
+    show_field("Section", section);
+  }
+  show_field("Maintainer", &package.maintainer);
+  show_field("Size", &package.size.to_string());
+  show_field(
+    "Source",
+    &format!("{} {} {}", source.url, source.distro, source.component),
+  );
+  match installed.and_then(|installed| installed.status.as_ref()) {
+    Some(status) => show_field(
+      "Installed",
+      &format!("{} ({})", installed.unwrap().version, status),
+    ),
+    None => show_field("Installed", "(none)"),
+  }
+
+  let pre_depends: Vec<String> = package
+    .depends
+    .iter()
+    .filter(|anyof| anyof.depends[0].dep_type == DepType::PreDepends)
+    .map(|anyof| anyof.to_string())
+    .collect();
+  if !pre_depends.is_empty() {
+    show_field("Pre-Depends", &pre_depends.join(", "));
+  }
+  let depends: Vec<String> = package
+    .depends
+    .iter()
+    .filter(|anyof| anyof.depends[0].dep_type == DepType::Depends)
+    .map(|anyof| anyof.to_string())
+    .collect();
+  if !depends.is_empty() {
+    show_field("Depends", &depends.join(", "));
+  }
+  for (field, relations) in [
+    ("Recommends", &package.recommends),
+    ("Suggests", &package.suggests),
+  ] {
+    if !relations.is_empty() {
+      let relations: Vec<String> = relations.iter().map(|r| r.to_string()).collect();
+      show_field(field, &relations.join(", "));
+    }
+  }
+
+  if !package.provides.is_empty() {
+    let provides: Vec<String> = package.provides.iter().map(|p| p.to_string()).collect();
+    show_field("Provides", &provides.join(", "));
+  }
+  for (field, relations) in [
+    ("Conflicts", &package.conflicts),
+    ("Breaks", &package.breaks),
+    ("Replaces", &package.replaces),
+  ] {
+    if !relations.is_empty() {
+      let relations: Vec<String> = relations.iter().map(|r| r.to_string()).collect();
+      show_field(field, &relations.join(", "));
+    }
+  }
+
+  show_field("Filename", &package.filename);
+  show_field("MD5sum", &package.md5);
+  show_field("SHA1", &package.sha1);
+  show_field("SHA256", &package.sha256);
+  show_field("Description", &package.short_description);
+  if let Some(long_description) = &package.long_description {
+    for line in long_description.lines() {
+      // a line with only "." means an empty line.
+      if line.trim() == "." {
+        println!();
+      } else {
+        println!("  {}", line.trim());
+      }
+    }
+  }
+}
+
+fn show_versions(
+  candidates: &[PackageWithSource],
+  target: &PackageWithSource,
+  candidate_by_policy: Option<&PackageWithSource>,
+  installed: Option<&Package>,
+) {
+  println!(
+    "\n{} Available versions({}):",
+    EMOJI_BOOKMARK,
+    style(candidates.len()).yellow()
+  );
+  for candidate in candidates {
+    let package = &candidate.package;
+    let source = &candidate.source;
+    let mut marks = vec![];
+    if package.version == target.package.version && source == &target.source {
+      marks.push("selected");
+    }
+    if let Some(candidate_by_policy) = candidate_by_policy {
+      if package.version == candidate_by_policy.package.version
+        && source == &candidate_by_policy.source
+      {
+        marks.push("candidate");
+      }
+    }
+    if let Some(installed) = installed {
+      if installed.status.is_some() && installed.version == package.version {
+        marks.push("installed");
+      }
+    }
+    println!(
+      "\t{} / {} {} {}",
+      style(&package.version).cyan(),
+      style(&source.distro).dim(),
+      style(&source.url).dim(),
+      style(marks.join(", ")).green(),
+    );
+  }
+}
@@ -4,6 +4,7 @@
 
 use super::{super::error::RaptError, UpdateArgs};
 use crate::{
+  apt::preferences::CandidatePolicy,
   context::Context,
   dpkg::{self, client::PackageStatus},
   net::package::PackageDownloadClient,
@@ -29,16 +30,22 @@ pub fn execute(context: &Context, _args: &UpdateArgs) -> Result<(), RaptError> {
     EMOJI_BOOKS,
   );
   let source_client = SourceClient::new(context.source_dir.clone())?;
-  let sources = source_client.read_all()?;
+  let sources = source_client.read_all_for(&context.architectures())?;
   // `update` would consider only binary packages?
   let target_sources: Vec<Source> = sources
     .into_iter()
     .filter(|source| source.archive_type == ArchivedType::DEB)
     .collect();
 
-  let total_sources_num = target_sources.len();
-  let mut total_packages: HashSet<Package> = HashSet::new();
-  let mut downloader = PackageDownloadClient::new(target_sources, context.list_dir.clone())?;
+  let mut downloader = PackageDownloadClient::new(
+    target_sources.clone(),
+    context.list_dir.clone(),
+    &context.source_dir,
+  )?;
+  let pool = context.download_pool()?;
+  if context.verbose {
+    show_net_config_verbose(context.net_config()?);
+  }
 
   // fetch InRelease and save its cache.
   println!(
@@ -46,18 +53,7 @@ pub fn execute(context: &Context, _args: &UpdateArgs) -> Result<(), RaptError> {
     style("[2/4]").bold().dim(),
     EMOJI_BOOKMARK,
   );
-  let progress = default_progbar(total_sources_num as u64);
-  loop {
-    progress.set_position(downloader.get_done_inrelease_num() as u64);
-    match downloader.get_next_target_source_inrelease() {
-      Some(source) => {
-        progress.set_message(source.inrelease_url());
-        let _ = downloader.get_inrelease_ifneed().unwrap();
-      }
-      None => break,
-    }
-  }
-  progress.abandon_with_message("Complete");
+  downloader.fetch_all_inrelease(&pool)?;
 
   // fetch all packages
   println!(
@@ -65,20 +61,9 @@ pub fn execute(context: &Context, _args: &UpdateArgs) -> Result<(), RaptError> {
     style("[3/4]").bold().dim(),
     EMOJI_BOOKMARK,
   );
-  let progress = default_progbar(total_sources_num as u64);
-  loop {
-    progress.set_position(downloader.get_done_packages_num() as u64);
-    match downloader.get_next_target_source_packages() {
-      Some(source) => {
-        progress.set_message(source.packages_url());
-        let package_content = downloader.get_package_ifneed()?.unwrap();
-        let packages = to_packages(&package_content, EntryType::BINARY)?;
-        Package::extend(&mut total_packages, packages);
-      }
-      None => break,
-    }
+  for package_content in downloader.fetch_all_packages(&pool)? {
+    to_packages(&package_content, EntryType::BINARY)?;
   }
-  progress.abandon_with_message("Complete");
 
   // release lock
   drop(lock);
@@ -89,8 +74,20 @@ pub fn execute(context: &Context, _args: &UpdateArgs) -> Result<(), RaptError> {
     style("[4/4]").bold().dim(),
     EMOJI_GLASS,
   );
+  // candidates are selected based on apt preferences.
+  let mut package_client = PackageClient::new(context.list_dir.clone())?;
+  package_client.set_policy(CandidatePolicy::read(
+    &context.source_dir,
+    &target_sources,
+    &context.list_dir,
+  )?);
   let mut dpkg_client =
     dpkg::client::DpkgClient::new(context.dpkg_dir.clone(), context.extended_state.clone());
+  let total_packages: HashSet<Package> = package_client
+    .read_all_from_source_with_source(&target_sources, &dpkg_client.get_installed_packages()?)?
+    .into_iter()
+    .map(|pws| pws.package)
+    .collect();
   let obsolute_packages = dpkg_client.get_obsolute_packages(&total_packages)?;
 
   // show result
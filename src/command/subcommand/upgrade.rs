@@ -1,8 +1,12 @@
 use super::{super::error::RaptError, UpgradeArgs};
 use crate::{
-  algorithm::dag::*,
+  algorithm::{conflict::plan_conflicts, dag::*},
+  apt::preferences::CandidatePolicy,
   context::Context,
-  dpkg::{client::DpkgClient, installer::DpkgInstaller},
+  dpkg::{
+    client::{DpkgClient, StatusComp},
+    installer::DpkgInstaller,
+  },
   net::binary::BinaryDownloader,
   package::client::{PackageClient, PackageWithSource},
   source::{client::SourceClient, source::Source},
@@ -25,13 +29,23 @@ pub fn execute(context: &Context, _args: &UpgradeArgs) -> Result<(), RaptError>
   );
 
   let source_client = SourceClient::new(PathBuf::from(&context.source_dir))?;
-  let sources: Vec<Source> = source_client.read_all()?.into_iter().collect();
+  let sources: Vec<Source> = source_client
+    .read_all_for(&context.architectures())?
+    .into_iter()
+    .collect();
   let mut package_client = PackageClient::new(PathBuf::from(&context.list_dir))?;
-  let pwss = package_client.read_all_from_source_with_source(&sources)?;
+  package_client.set_policy(CandidatePolicy::read(
+    &context.source_dir,
+    &sources,
+    &context.list_dir,
+  )?);
   let mut dpkg_client = DpkgClient::new(
     PathBuf::from(&context.dpkg_dir),
     context.extended_state.clone(),
   );
+  // installed versions kept by preferences are never upgraded.
+  let pwss = package_client
+    .read_all_from_source_with_source(&sources, &dpkg_client.get_installed_packages()?)?;
   let obsolute_packages =
     dpkg_client.get_obsolute_packages(&pwss.iter().map(|pws| pws.package.clone()).collect())?;
   if obsolute_packages.is_empty() {
@@ -57,7 +71,7 @@ pub fn execute(context: &Context, _args: &UpgradeArgs) -> Result<(), RaptError>
     };
     println!(
       "\t- {} ({} -> {})",
-      style(package.package.name.clone()).yellow(),
+      style(package.package.full_name()).yellow(),
       style(package.package.version.to_string()).dim(),
       style(new_version).dim(),
     );
@@ -70,7 +84,7 @@ pub fn execute(context: &Context, _args: &UpgradeArgs) -> Result<(), RaptError>
     .map(|package| {
       pwss
         .iter()
-        .find(|pws| pws.package.name == package.package.name)
+        .find(|pws| pws.package == package.package)
         .unwrap()
         .clone()
     })
@@ -83,7 +97,7 @@ pub fn execute(context: &Context, _args: &UpgradeArgs) -> Result<(), RaptError>
     // sort node reachable from current target
     let current_sorted_pwss: Vec<PackageWithSource> = sort_depends(
       target_pwss.clone().into_iter().collect(),
-      &target_pwss[0].package.name,
+      &target_pwss[0].package.full_name(),
     )?;
     // remove sorted targets
     let mut removable_ixs = vec![];
@@ -103,6 +117,10 @@ pub fn execute(context: &Context, _args: &UpgradeArgs) -> Result<(), RaptError>
   drop(target_pwss);
   let layers = split_layers(&sorted_pwss);
 
+  // plan removals and deconfigurations before touching dpkg.
+  let conflict_plan = plan_conflicts(&sorted_pwss, &dpkg_client.get_installed_packages()?)?;
+  show_conflict_plan(&conflict_plan);
+
   if context.verbose {
     show_deps_verbose(&layers);
   }
@@ -118,22 +136,14 @@ pub fn execute(context: &Context, _args: &UpgradeArgs) -> Result<(), RaptError>
     style("[2/3]").bold().dim(),
     EMOJI_EARTH,
   );
-  let progress = default_progbar(sorted_pwss.len() as u64);
-
+  if context.verbose {
+    show_net_config_verbose(context.net_config()?);
+  }
   let binary_client = BinaryDownloader::new(
     sorted_pwss.clone().into_iter().collect(),
     PathBuf::from(&context.archive_dir),
   )?;
-  for downloader in binary_client.into_iter() {
-    progress.set_message(
-      style(downloader.pws.package.name.clone())
-        .cyan()
-        .to_string(),
-    );
-    downloader.download()?;
-    progress.inc(1);
-  }
-  progress.abandon_with_message("Complete.");
+  binary_client.download_all(&context.download_pool()?)?;
 
   // release lock
   drop(lock);
@@ -151,11 +161,27 @@ pub fn execute(context: &Context, _args: &UpgradeArgs) -> Result<(), RaptError>
   let progress = ProgressBar::new(sorted_pwss.len() as u64 * 2);
   progress.set_style(prog_style);
 
+  // remove conflicting packages which dpkg doesn't replace by itself.
+  for removal in conflict_plan.explicit_removals() {
+    progress.println(format!(
+      " remove    {} (conflicts with {})",
+      removal.package.name, removal.cause
+    ));
+    dpkg_client.remove_package(&removal.package, false)?;
+  }
+
   for layer in layers.into_iter().rev() {
     let dpkg_client = DpkgInstaller::new(
       PathBuf::from(&context.archive_dir),
       layer.into_iter().rev().collect(),
+      // new dependencies are marked as automatically installed, and upgraded ones are kept as they are.
+      sorted_pwss
+        .iter()
+        .filter(|pws| pws.dpkg_status == Some(StatusComp::NOTINSTALLED))
+        .map(|pws| pws.package.full_name())
+        .collect(),
       vec![],
+      conflict_plan.breaking_packages(),
       context.extended_state.clone(),
     )?;
     for extracter in dpkg_client.extracters_iter() {
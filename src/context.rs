@@ -2,21 +2,31 @@
  This file defines global context information and app argument structure.
 */
 
+use crate::apt::config::ConfigError;
 use crate::command::subcommand::SubCommand as RaptSubCommand;
+use crate::dpkg::arch::Architectures;
+use crate::net::config::NetConfig;
+use crate::net::pool::{DownloadPool, DEFAULT_JOBS};
+use crate::net::transport::{DefaultTransport, Transport};
 use clap::Parser;
+use once_cell::sync::OnceCell;
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct Context {
-  pub list_dir: PathBuf,       // package list dir
-  pub source_dir: PathBuf,     // source list dir
-  pub dpkg_dir: PathBuf,       // dpkg base dir
-  pub dpkg_lock: PathBuf,      // dpkg frontend lock
-  pub lists_lock: PathBuf,     // list cache lock
-  pub archive_dir: PathBuf,    // binary deb file archive dir
-  pub extended_state: PathBuf, // apt extended state path
-  pub verbose: bool,           // verbose output flag
+  pub list_dir: PathBuf,                     // package list dir
+  pub source_dir: PathBuf,                   // source list dir
+  pub dpkg_dir: PathBuf,                     // dpkg base dir
+  pub dpkg_lock: PathBuf,                    // dpkg frontend lock
+  pub lists_lock: PathBuf,                   // list cache lock
+  pub archive_dir: PathBuf,                  // binary deb file archive dir
+  pub extended_state: PathBuf,               // apt extended state path
+  pub verbose: bool,                         // verbose output flag
+  pub jobs: usize,                           // number of parallel downloads
+  pub net_config: OnceCell<NetConfig>, // proxies, timeouts and retries, read on the first download
+  pub transport: Option<Arc<dyn Transport>>, // how to fetch indices and archives, `None` for default
 }
 
 impl Default for Context {
@@ -40,10 +50,38 @@ impl Default for Context {
       dpkg_lock,
       extended_state,
       verbose: false,
+      jobs: DEFAULT_JOBS,
+      net_config: OnceCell::new(),
+      transport: None,
     }
   }
 }
 
+impl Context {
+  // native and foreign architectures configured in dpkg.
+  pub fn architectures(&self) -> Architectures {
+    Architectures::read(&self.dpkg_dir)
+  }
+
+  // network config is read from apt.conf in source dir only by commands which download,
+  // so a broken apt.conf doesn't fail the others.
+  pub fn net_config(&self) -> Result<&NetConfig, ConfigError> {
+    self
+      .net_config
+      .get_or_try_init(|| NetConfig::read(&self.source_dir))
+  }
+
+  // worker pool of parallel downloads by the transport of this context.
+  pub fn download_pool(&self) -> Result<DownloadPool, ConfigError> {
+    let net_config = self.net_config()?;
+    let transport = match &self.transport {
+      Some(transport) => transport.clone(),
+      None => Arc::new(DefaultTransport::new(net_config.clone())),
+    };
+    Ok(DownloadPool::new(self.jobs, transport).with_retry_policy(net_config.retry_policy))
+  }
+}
+
 #[derive(Parser, Debug)]
 #[clap(about, version)]
 pub struct Args {
@@ -73,6 +111,9 @@ pub struct Args {
 
   #[clap(long, help = "verbose output.")]
   pub verbose: bool,
+
+  #[clap(short, long, help = "number of parallel downloads", default_value_t = DEFAULT_JOBS)]
+  pub jobs: usize,
 }
 
 impl Args {
@@ -109,6 +150,8 @@ impl Args {
       context.verbose = true;
     };
 
+    context.jobs = self.jobs.max(1);
+
     context
   }
 }
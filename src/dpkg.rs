@@ -1,3 +1,4 @@
+pub mod arch;
 pub mod client;
 pub mod installer;
 pub mod status;
@@ -0,0 +1,93 @@
+/*
+ This file defines architectures handled by dpkg.
+ Native architecture is the one dpkg is built for,
+ and foreign architectures are ones added by `dpkg --add-architecture`.
+*/
+
+use once_cell::sync::Lazy;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// native architecture of dpkg, which is detected only once.
+static NATIVE_ARCH: Lazy<String> = Lazy::new(detect_native_arch);
+
+pub fn native_arch() -> &'static str {
+  &NATIVE_ARCH
+}
+
+// ask dpkg for native architecture.
+// If dpkg is not available, it is guessed from the architecture rapt2 is built for.
+fn detect_native_arch() -> String {
+  if let Ok(output) = Command::new("dpkg").arg("--print-architecture").output() {
+    let arch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !arch.is_empty() {
+      return arch;
+    }
+  }
+  match std::env::consts::ARCH {
+    "x86_64" => "amd64",
+    "x86" => "i386",
+    "aarch64" => "arm64",
+    "arm" => "armhf",
+    "powerpc64" => "ppc64el",
+    "s390x" => "s390x",
+    "riscv64" => "riscv64",
+    arch => arch,
+  }
+  .to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Architectures {
+  pub native: String,
+  pub foreign: Vec<String>,
+}
+
+impl Default for Architectures {
+  fn default() -> Self {
+    Self {
+      native: native_arch().into(),
+      foreign: vec![],
+    }
+  }
+}
+
+impl Architectures {
+  // read foreign architectures from `arch` file in dpkg dir,
+  // which is equivalent to `dpkg --print-foreign-architectures`.
+  pub fn read(dpkg_dir: &Path) -> Self {
+    let mut archs = Self::default();
+    if let Ok(content) = fs::read_to_string(dpkg_dir.join("arch")) {
+      for arch in content.lines().map(|line| line.trim()) {
+        if !arch.is_empty() && arch != archs.native && !archs.foreign.iter().any(|f| f == arch) {
+          archs.foreign.push(arch.into());
+        }
+      }
+    }
+    archs
+  }
+
+  // all architectures, native one first.
+  pub fn all(&self) -> Vec<String> {
+    let mut archs = vec![self.native.clone()];
+    archs.extend(self.foreign.iter().cloned());
+    archs
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_read_architectures() {
+    let archs = Architectures::read(Path::new("./tests/resources/dpkg"));
+    assert_eq!(archs.native, native_arch());
+    assert_eq!(archs.foreign, vec!["i386"]);
+    assert_eq!(archs.all(), vec![native_arch(), "i386"]);
+
+    let archs = Architectures::read(Path::new("./tests/resources/not-exist"));
+    assert!(archs.foreign.is_empty());
+  }
+}
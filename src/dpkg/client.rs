@@ -64,6 +64,7 @@ impl DpkgClient {
 
   // Get packages which are:
   //    - installed but but have older version
+  //    - installed but newer than candidate, which is pinned to be downgraded by preferences
   //    - not installed
   // Returned `package` is old one.
   pub fn get_obsolute_packages(
@@ -108,6 +109,12 @@ impl DpkgClient {
           status: StatusComp::OLD(package.version.clone()),
           new_version: Some(candidate_new.version.clone()),
         });
+      } else if candidate_new.version < package.version {
+        results.push(PackageStatus {
+          package: package.clone(),
+          status: StatusComp::NEWER(package.version.clone()),
+          new_version: Some(candidate_new.version.clone()),
+        });
       }
     }
 
@@ -130,26 +137,7 @@ impl DpkgClient {
 
   pub fn check_installed_status(&mut self, target: &Package) -> Result<StatusComp, PackageError> {
     let installeds = self.get_installed_packages()?;
-    match installeds
-      .iter()
-      .find(|installed| installed.name == target.name)
-    {
-      Some(package) => {
-        // check only installed packages
-        if let Some(status) = &package.status {
-          if status.status != DpkgStatusStatus::Installed {
-            return Ok(StatusComp::NOTINSTALLED);
-          }
-        }
-        // compare version
-        if package.version < target.version {
-          Ok(StatusComp::OLD(package.version.clone()))
-        } else {
-          Ok(StatusComp::UPTODATE)
-        }
-      }
-      None => Ok(StatusComp::NOTINSTALLED),
-    }
+    Ok(compare_with_installed(&installeds, target))
   }
 
   // XXX must update extended_states
@@ -162,7 +150,7 @@ impl DpkgClient {
       .output()
       .unwrap();
     if output.status.success() {
-      extended_state_client.update(&package.name, false)?;
+      extended_state_client.update(&package.name, &package.arch, false)?;
       Ok(())
     } else {
       let errstr = String::from_utf8(output.stderr).unwrap();
@@ -174,10 +162,35 @@ impl DpkgClient {
   }
 }
 
+// compare `target` with its installed version in `installeds`, which is read from dpkg status DB.
+pub fn compare_with_installed(installeds: &HashSet<Package>, target: &Package) -> StatusComp {
+  // packages of different architectures are distinguished.
+  match installeds.get(target) {
+    Some(package) => {
+      // check only installed packages
+      if let Some(status) = &package.status {
+        if status.status != DpkgStatusStatus::Installed {
+          return StatusComp::NOTINSTALLED;
+        }
+      }
+      // compare version
+      if package.version < target.version {
+        StatusComp::OLD(package.version.clone())
+      } else if package.version > target.version {
+        StatusComp::NEWER(package.version.clone())
+      } else {
+        StatusComp::UPTODATE
+      }
+    }
+    None => StatusComp::NOTINSTALLED,
+  }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum StatusComp {
   NOTINSTALLED,
   OLD(version::Version),
+  NEWER(version::Version), // installing `target` means downgrade
   UPTODATE,
 }
 
@@ -221,4 +234,27 @@ mod tests {
     );
     assert_eq!(obsolute_packages[0].package.name, "vim");
   }
+
+  #[test]
+  fn test_check_installed_status() {
+    let mut dpkg_client = DpkgClient::new(
+      PathBuf::from("./tests/resources/dpkg"),
+      PathBuf::from("/var/lib/apt/extended_states"),
+    );
+    let mut vim = Package {
+      name: "vim".into(),
+      version: Version::from("2:8.1.2268-1ubuntu5").unwrap(),
+      ..Default::default()
+    };
+    assert_eq!(
+      dpkg_client.check_installed_status(&vim).unwrap(),
+      StatusComp::UPTODATE
+    );
+    // installing older version means downgrade.
+    vim.version = Version::from("2:8.0.0000").unwrap();
+    assert_eq!(
+      dpkg_client.check_installed_status(&vim).unwrap(),
+      StatusComp::NEWER(Version::from("2:8.1.2268-1ubuntu5").unwrap())
+    );
+  }
 }
@@ -12,7 +12,9 @@ use std::{path::PathBuf, process::Command};
 pub struct DpkgInstaller {
   archive_dir: PathBuf,
   pub pwss: Vec<PackageWithSource>, // packages to be installed
-  automatics: Vec<String>,          // names of automatically installed packages
+  automatics: Vec<String>,          // qualified names of automatically installed packages
+  manuals: Vec<String>,             // qualified names of manually installed packages
+  breakings: Vec<String>,           // names of packages which break installed packages
   extended_state: PathBuf,          // apt extended_state path
 }
 
@@ -20,14 +22,17 @@ pub struct DpkgExtracterIter {
   archive_dir: PathBuf,
   curr: usize,
   pub pwss: Vec<PackageWithSource>,
-  automatics: Vec<String>, // names of automatically installed packages
+  automatics: Vec<String>, // qualified names of automatically installed packages
+  manuals: Vec<String>,    // qualified names of manually installed packages
+  breakings: Vec<String>,  // names of packages which break installed packages
   extended_state: PathBuf,
 }
 
 pub struct DpkgExtracter {
   archive_dir: PathBuf,
   pub pws: PackageWithSource,
-  is_automatic: bool,
+  auto_installed: Option<bool>, // `None` keeps the record in extended_states as it is
+  is_breaking: bool,            // if true, broken installed packages are deconfigured
   extended_state: PathBuf,
 }
 
@@ -52,7 +57,8 @@ impl Iterator for DpkgExtracterIter {
     Some(Self::Item {
       pws: self.pwss[ix].clone(),
       archive_dir: self.archive_dir.clone(),
-      is_automatic: self.automatics.contains(&self.pwss[ix].package.name),
+      auto_installed: auto_installed(&self.pwss[ix], &self.automatics, &self.manuals),
+      is_breaking: self.breakings.contains(&self.pwss[ix].package.name),
       extended_state: self.extended_state.clone(),
     })
   }
@@ -72,13 +78,16 @@ impl DpkgExtracter {
       });
     }
 
-    // XXX should parse `Break` field instead using `--auto-deconfigure`.
-    let output = Command::new("dpkg")
-      .args(&["--auto-deconfigure", "--unpack", &archived_fullname])
-      .output()
-      .unwrap();
+    // deconfigure broken packages only when planned.
+    let mut args = vec!["--unpack", &archived_fullname];
+    if self.is_breaking {
+      args.insert(0, "--auto-deconfigure");
+    }
+    let output = Command::new("dpkg").args(&args).output().unwrap();
     if output.status.success() {
-      extended_state_client.update(&package.name, self.is_automatic)?;
+      if let Some(auto_installed) = self.auto_installed {
+        extended_state_client.update(&package.name, &package.arch, auto_installed)?;
+      }
       Ok(())
     } else {
       let errstr = String::from_utf8(output.stderr).unwrap();
@@ -108,9 +117,15 @@ impl Iterator for DpkgConfigurerIter {
 impl DpkgConfigurer {
   pub fn execute(&self) -> Result<(), PackageError> {
     let package = &self.pws.package;
+    // qualify with architecture, cuz `Multi-Arch: same` package may be installed for several ones.
+    let target = if package.arch.is_empty() {
+      package.name.clone()
+    } else {
+      format!("{}:{}", package.name, package.arch)
+    };
 
     let output = Command::new("dpkg")
-      .args(&["--configure", &package.name])
+      .args(["--configure", &target])
       .output()
       .unwrap();
     if output.status.success() {
@@ -130,6 +145,8 @@ impl DpkgInstaller {
     archive_dir: PathBuf,
     pwss: Vec<PackageWithSource>,
     automatics: Vec<String>,
+    manuals: Vec<String>,
+    breakings: Vec<String>,
     extended_state: PathBuf,
   ) -> Result<Self, PackageError> {
     if !archive_dir.as_path().is_dir() {
@@ -142,6 +159,8 @@ impl DpkgInstaller {
       archive_dir,
       pwss,
       automatics,
+      manuals,
+      breakings,
       extended_state,
     })
   }
@@ -152,6 +171,8 @@ impl DpkgInstaller {
       pwss: self.pwss.clone(),
       curr: 0,
       automatics: self.automatics.clone(),
+      manuals: self.manuals.clone(),
+      breakings: self.breakings.clone(),
       extended_state: self.extended_state.clone(),
     }
   }
@@ -163,3 +184,19 @@ impl DpkgInstaller {
     }
   }
 }
+
+// whether `pws` is marked as automatically installed, or `None` if neither of them.
+fn auto_installed(
+  pws: &PackageWithSource,
+  automatics: &[String],
+  manuals: &[String],
+) -> Option<bool> {
+  let name = pws.package.full_name();
+  if manuals.contains(&name) {
+    Some(false)
+  } else if automatics.contains(&name) {
+    Some(true)
+  } else {
+    None
+  }
+}
@@ -22,6 +22,12 @@ impl DpkgStatusArea {
   }
 }
 
+impl std::fmt::Display for DpkgStatusArea {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {} {}", self.want, self.flag, self.status)
+  }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum DpkgStatusWant {
   INSTALL,
@@ -31,6 +37,18 @@ pub enum DpkgStatusWant {
   UNKNOWN,
 }
 
+impl std::fmt::Display for DpkgStatusWant {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::INSTALL => write!(f, "install"),
+      Self::HOLD => write!(f, "hold"),
+      Self::DEINSTALL => write!(f, "deinstall"),
+      Self::PURGE => write!(f, "purge"),
+      Self::UNKNOWN => write!(f, "unknown"),
+    }
+  }
+}
+
 impl DpkgStatusWant {
   pub fn from(s: &str) -> Self {
     match s {
@@ -53,6 +71,18 @@ pub enum DpkgStatusFlag {
   Unknown,
 }
 
+impl std::fmt::Display for DpkgStatusFlag {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::ReinstReq => write!(f, "reinstreq"),
+      Self::Hold => write!(f, "hold"),
+      Self::HoldReinstReq => write!(f, "hold-reinstreq"),
+      Self::Ok => write!(f, "ok"),
+      Self::Unknown => write!(f, "unknown"),
+    }
+  }
+}
+
 impl DpkgStatusFlag {
   pub fn from(s: &str) -> Self {
     match s {
@@ -80,6 +110,23 @@ pub enum DpkgStatusStatus {
   Unknown,
 }
 
+impl std::fmt::Display for DpkgStatusStatus {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Installed => write!(f, "installed"),
+      Self::NotInstalled => write!(f, "not-installed"),
+      Self::Unpacked => write!(f, "unpacked"),
+      Self::HalfConfigured => write!(f, "half-configured"),
+      Self::HalfInstalled => write!(f, "half-installed"),
+      Self::ConfigFiles => write!(f, "config-files"),
+      Self::PostInstFailed => write!(f, "post-inst-failed"),
+      Self::RemovalFailed => write!(f, "removal-failed"),
+      Self::Removed => write!(f, "removed"),
+      Self::Unknown => write!(f, "unknown"),
+    }
+  }
+}
+
 impl DpkgStatusStatus {
   pub fn from(s: &str) -> Self {
     match s {
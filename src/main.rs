@@ -5,8 +5,7 @@ use clap::Parser;
 
 fn main() {
   let args = Args::parse();
-  let context = args.to_context();
-  let rapt = Rapt::new(context, args.command);
+  let rapt = Rapt::new(args.to_context(), args.command);
 
   rapt.execute();
 }
@@ -1,3 +1,13 @@
 pub mod binary;
+pub mod checksum;
+pub mod compression;
+pub mod config;
 pub mod error;
+pub mod local;
+pub mod mirror;
+pub mod mock;
 pub mod package;
+pub mod pdiff;
+pub mod pool;
+pub mod signature;
+pub mod transport;
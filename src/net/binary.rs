@@ -1,25 +1,29 @@
 /*
  This file defines a downloader of binary packages(.deb).
+ Archives are downloaded into `partial` dir of archive dir first,
+ and moved into archive dir atomically after verified.
+ Interrupted downloads left in `partial` dir are resumed by `Range` request.
 */
 
+use super::checksum::verify_checksum;
 use super::error::DownloadError;
-use crate::package::client::PackageWithSource;
+use super::mirror::with_failover;
+use super::pool::DownloadPool;
+use super::transport::{RangeBody, Transport};
+use crate::package::{client::PackageWithSource, package::Package};
+use crate::source::source::Source;
 
+use console::style;
 use reqwest::StatusCode;
 use std::fs;
-use std::io::Cursor;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const PARTIAL_DIR: &str = "partial";
 
 pub struct BinaryDownloader {
   packages: Vec<PackageWithSource>,
   cache_dir: PathBuf,
-  curr: usize,
-}
-
-pub struct BinaryDownloaderExecuter {
-  pub pws: PackageWithSource,
-  cache_dir: PathBuf,
-  client: reqwest::blocking::Client,
 }
 
 impl BinaryDownloader {
@@ -30,58 +34,306 @@ impl BinaryDownloader {
         name: cache_dir.to_string_lossy().to_string(),
       });
     }
+    let partial_dir = cache_dir.join(PARTIAL_DIR);
+    if !partial_dir.is_dir() && std::fs::create_dir(&partial_dir).is_err() {
+      return Err(DownloadError::FileNotFound {
+        name: partial_dir.to_string_lossy().to_string(),
+      });
+    }
 
     Ok(Self {
       packages,
       cache_dir,
-      curr: 0,
     })
   }
+
+  // download all packages in parallel.
+  // If some downloads fail, the error of the first package in order is returned.
+  pub fn download_all(&self, pool: &DownloadPool) -> Result<(), DownloadError> {
+    pool
+      .run(
+        &self.packages,
+        |pws| {
+          (
+            archive_url(&pws.source, &pws.package),
+            style(&pws.package.name).cyan().to_string(),
+          )
+        },
+        |transport, pws| {
+          with_failover(&pws.source, pool.retry_policy(), |mirror| {
+            download(transport, mirror, &pws.package, &self.cache_dir)
+          })
+        },
+      )
+      .into_iter()
+      .collect::<Result<Vec<()>, DownloadError>>()?;
+    Ok(())
+  }
 }
 
-impl BinaryDownloaderExecuter {
-  pub fn download(&self) -> Result<(), DownloadError> {
-    let package = &self.pws.package;
-    let source = &self.pws.source;
-    let url = format!("{}{}", source.url, package.filename);
-    let builder = self.client.get(url);
-    let filename = package.filename.split('/').last().unwrap();
-    let filepath = self.cache_dir.join(filename);
-
-    match builder.send() {
-      Ok(res) => {
-        if res.status() == StatusCode::OK {
-          let bytes = res.bytes()?;
-          let mut cache_file = fs::File::create(filepath)?;
-          let mut content = Cursor::new(bytes);
-          std::io::copy(&mut content, &mut cache_file)?;
-        } else {
-          return Err(DownloadError::InvalidStatusCode {
-            status: res.status(),
-          });
+fn archive_url(source: &Source, package: &Package) -> String {
+  format!("{}{}", source.url, package.filename)
+}
+
+// download .deb from `source` into `cache_dir` unless verified one is already cached.
+fn download(
+  transport: &dyn Transport,
+  source: &Source,
+  package: &Package,
+  cache_dir: &Path,
+) -> Result<(), DownloadError> {
+  let filename = package.filename.split('/').last().unwrap();
+  let filepath = cache_dir.join(filename);
+
+  // reuse cached archive if it is verified, otherwise discard it.
+  if filepath.is_file() {
+    if verify(package, &fs::read(&filepath)?).is_ok() {
+      return Ok(());
+    }
+    fs::remove_file(&filepath)?;
+  }
+
+  // resume interrupted download if exists.
+  let partial_path = cache_dir.join(PARTIAL_DIR).join(filename);
+  let url = archive_url(source, package);
+  let partial_len = match fs::metadata(&partial_path) {
+    Ok(meta) if meta.len() < package.size && is_resumable(transport, &url, &meta) => meta.len(),
+    Ok(_) => {
+      // partial file is complete, larger than expected or older than the archive,
+      // so never resumable.
+      fs::remove_file(&partial_path)?;
+      0
+    }
+    Err(_) => 0,
+  };
+
+  let body = match transport.fetch_range(&url, partial_len) {
+    Ok(body) => body,
+    Err(err) => {
+      // partial file seems to be stale, so restart from scratch on the next run.
+      if let DownloadError::InvalidStatusCode { status } = &err {
+        if *status == StatusCode::RANGE_NOT_SATISFIABLE {
+          fs::remove_file(&partial_path)?;
         }
       }
-      Err(err) => return Err(DownloadError::RequestFailed(err)),
+      return Err(err);
     }
+  };
+  match body {
+    // server supports `Range`, so append the rest.
+    RangeBody::Partial(bytes) => fs::OpenOptions::new()
+      .append(true)
+      .open(&partial_path)?
+      .write_all(&bytes)?,
+    RangeBody::Whole(bytes) => fs::write(&partial_path, bytes)?,
+  }
 
-    Ok(())
+  // verify and move it into archive dir.
+  if let Err(err) = verify(package, &fs::read(&partial_path)?) {
+    fs::remove_file(&partial_path)?;
+    return Err(err);
+  }
+  fs::rename(&partial_path, &filepath)?;
+
+  Ok(())
+}
+
+// whether the partial file can be continued, ie) the archive is not modified since it's written.
+// If modified time of either one is unknown, it's regarded as resumable.
+fn is_resumable(transport: &dyn Transport, url: &str, partial: &fs::Metadata) -> bool {
+  let remote = transport.metadata(url).ok().and_then(|meta| meta.modified);
+  match (remote, partial.modified().ok()) {
+    (Some(remote), Some(partial)) => remote <= partial,
+    _ => true,
   }
 }
 
-impl Iterator for BinaryDownloader {
-  type Item = BinaryDownloaderExecuter;
+// check size and SHA256 of the archive against `Packages` entry.
+fn verify(package: &Package, bytes: &[u8]) -> Result<(), DownloadError> {
+  let invalid = |reason: String| DownloadError::InvalidArchive {
+    filename: package.filename.clone(),
+    reason,
+  };
+  if package.sha256.is_empty() {
+    return Err(invalid("SHA256 is not given in package index".into()));
+  }
+  verify_checksum(bytes, package.size, &package.sha256, &package.filename)
+    .map_err(|err| invalid(err.to_string()))
+}
 
-  fn next(&mut self) -> Option<Self::Item> {
-    if self.curr >= self.packages.len() {
-      return None;
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::net::mock::MockTransport;
+  use crate::net::transport::HttpTransport;
+  use crate::source::source::{ArchivedType, Component, Source};
+  use std::io::Read;
+  use std::net::TcpListener;
+  use std::thread;
+  use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+  fn package_with_source(url: &str) -> PackageWithSource {
+    let package = Package {
+      name: "hello".into(),
+      filename: "pool/main/h/hello/hello_1.0_amd64.deb".into(),
+      size: 5,
+      sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".into(),
+      ..Default::default()
+    };
+    let source = Source {
+      archive_type: ArchivedType::DEB,
+      url: url.into(),
+      distro: "/".into(),
+      component: Component::NULL,
+      options: Default::default(),
+      mirrors: vec![],
+    };
+    PackageWithSource {
+      package,
+      source,
+      dpkg_status: None,
     }
-    let client = reqwest::blocking::Client::builder().build().unwrap();
-    let ix = self.curr;
-    self.curr += 1;
-    Some(Self::Item {
-      pws: self.packages[ix].clone(),
-      client,
-      cache_dir: self.cache_dir.clone(),
-    })
+  }
+
+  #[test]
+  fn test_reuse_verified_archive() {
+    let cache_dir = std::env::temp_dir().join(format!("rapt2-binary-test-{}", std::process::id()));
+    fs::create_dir_all(cache_dir.join(PARTIAL_DIR)).unwrap();
+    let pws = package_with_source("http://127.0.0.1:9/");
+    let transport = HttpTransport::default();
+    let archive = cache_dir.join("hello_1.0_amd64.deb");
+
+    // verified cache is reused without any request.
+    fs::write(&archive, "hello").unwrap();
+    assert!(download(&transport, &pws.source, &pws.package, &cache_dir).is_ok());
+    assert!(archive.is_file());
+
+    // broken cache is discarded and re-downloaded.
+    fs::write(&archive, "hellO").unwrap();
+    assert!(matches!(
+      verify(&pws.package, b"hellO"),
+      Err(DownloadError::InvalidArchive { .. })
+    ));
+    assert!(download(&transport, &pws.source, &pws.package, &cache_dir).is_err());
+    assert!(!archive.exists());
+
+    fs::remove_dir_all(&cache_dir).unwrap();
+  }
+
+  // serve "hello" `times` times, with respecting `Range: bytes=<start>-`.
+  // Returns all requests received, where `HEAD` is answered without body.
+  fn serve(times: usize) -> (String, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+      let mut requests = String::new();
+      for _ in 0..times {
+        requests += &respond(listener.accept().unwrap().0);
+      }
+      requests
+    });
+    (url, handle)
+  }
+
+  fn respond(mut stream: std::net::TcpStream) -> String {
+    {
+      let mut request = vec![];
+      let mut buf = [0u8; 1024];
+      while !request.ends_with(b"\r\n\r\n") {
+        let n = stream.read(&mut buf).unwrap();
+        request.extend_from_slice(&buf[..n]);
+      }
+      let request = String::from_utf8(request).unwrap();
+      let start: usize = request
+        .lines()
+        .find_map(|line| {
+          line
+            .to_lowercase()
+            .strip_prefix("range: bytes=")
+            .map(String::from)
+        })
+        .map(|range| range.trim_end_matches('-').parse().unwrap())
+        .unwrap_or(0);
+      let body = &b"hello"[start..];
+      let status = if start == 0 {
+        "200 OK"
+      } else {
+        "206 Partial Content"
+      };
+      let header = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+      );
+      stream.write_all(header.as_bytes()).unwrap();
+      if !request.starts_with("HEAD") {
+        stream.write_all(body).unwrap();
+      }
+      request
+    }
+  }
+
+  #[test]
+  fn test_resume_partial_archive() {
+    let cache_dir = std::env::temp_dir().join(format!("rapt2-partial-test-{}", std::process::id()));
+    fs::create_dir_all(cache_dir.join(PARTIAL_DIR)).unwrap();
+    let transport = HttpTransport::default();
+    let partial = cache_dir.join(PARTIAL_DIR).join("hello_1.0_amd64.deb");
+    let archive = cache_dir.join("hello_1.0_amd64.deb");
+
+    // interrupted download is resumed, and moved into archive dir after verified.
+    fs::write(&partial, "hel").unwrap();
+    let (url, server) = serve(2);
+    let pws = package_with_source(&url);
+    assert!(download(&transport, &pws.source, &pws.package, &cache_dir).is_ok());
+    assert!(server
+      .join()
+      .unwrap()
+      .to_lowercase()
+      .contains("range: bytes=3-"));
+    assert!(!partial.exists());
+    assert_eq!(fs::read(&archive).unwrap(), b"hello");
+
+    // broken partial file is discarded.
+    fs::remove_file(&archive).unwrap();
+    fs::write(&partial, "HEL").unwrap();
+    let (url, server) = serve(2);
+    let pws = package_with_source(&url);
+    assert!(matches!(
+      download(&transport, &pws.source, &pws.package, &cache_dir),
+      Err(DownloadError::InvalidArchive { .. })
+    ));
+    server.join().unwrap();
+    assert!(!partial.exists());
+    assert!(!archive.exists());
+
+    fs::remove_dir_all(&cache_dir).unwrap();
+  }
+
+  #[test]
+  fn test_discard_stale_partial_archive() {
+    let cache_dir = std::env::temp_dir().join(format!("rapt2-stale-test-{}", std::process::id()));
+    fs::create_dir_all(cache_dir.join(PARTIAL_DIR)).unwrap();
+    let partial = cache_dir.join(PARTIAL_DIR).join("hello_1.0_amd64.deb");
+    let archive = cache_dir.join("hello_1.0_amd64.deb");
+    let pws = package_with_source("http://mock/");
+    let url = archive_url(&pws.source, &pws.package);
+    let transport = MockTransport::new();
+
+    // the archive is not modified since the partial file is written, so it's resumed.
+    transport.put_modified(&url, "hello", UNIX_EPOCH);
+    fs::write(&partial, "hel").unwrap();
+    assert!(download(&transport, &pws.source, &pws.package, &cache_dir).is_ok());
+    assert_eq!(fs::read(&archive).unwrap(), b"hello");
+
+    // the archive is replaced after the partial file is written, so it's fetched from scratch.
+    fs::remove_file(&archive).unwrap();
+    transport.put_modified(&url, "hello", SystemTime::now() + Duration::from_secs(3600));
+    fs::write(&partial, "HEL").unwrap();
+    assert!(download(&transport, &pws.source, &pws.package, &cache_dir).is_ok());
+    assert_eq!(fs::read(&archive).unwrap(), b"hello");
+    assert!(!partial.exists());
+
+    fs::remove_dir_all(&cache_dir).unwrap();
   }
 }
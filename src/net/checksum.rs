@@ -0,0 +1,59 @@
+/*
+ This file implements verification of downloaded files by size and SHA256.
+*/
+
+use super::error::DownloadError;
+
+use sha2::{Digest, Sha256};
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+  Sha256::digest(bytes)
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
+// check `bytes` of `target` have expected size and SHA256.
+pub fn verify_checksum(
+  bytes: &[u8],
+  size: u64,
+  sha256: &str,
+  target: &str,
+) -> Result<(), DownloadError> {
+  if bytes.len() as u64 != size {
+    return Err(DownloadError::SizeMismatch {
+      target: target.to_string(),
+      expected: size,
+      actual: bytes.len() as u64,
+    });
+  }
+  let actual = sha256_hex(bytes);
+  if actual != sha256.to_lowercase() {
+    return Err(DownloadError::HashMismatch {
+      target: target.to_string(),
+      expected: sha256.to_string(),
+      actual,
+    });
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_verify_checksum() {
+    let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    assert_eq!(sha256_hex(b"hello"), sha256);
+    assert!(verify_checksum(b"hello", 5, sha256, "hello").is_ok());
+    assert!(matches!(
+      verify_checksum(b"hello!", 5, sha256, "hello"),
+      Err(DownloadError::SizeMismatch { .. })
+    ));
+    assert!(matches!(
+      verify_checksum(b"hellO", 5, sha256, "hello"),
+      Err(DownloadError::HashMismatch { .. })
+    ));
+  }
+}
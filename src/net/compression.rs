@@ -0,0 +1,130 @@
+/*
+ This file implements compression variants of index files such as `Packages.xz`.
+ A variant to fetch is chosen from files listed in Release.
+*/
+
+use super::error::DownloadError;
+use crate::source::release::{Release, ReleaseFile};
+
+use flate2::read::GzDecoder;
+use std::io::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+  Xz,
+  Zstd,
+  Gzip,
+  Lz4,
+  None, // uncompressed
+}
+
+// variants in the order of preference: smaller files first, and uncompressed one at last.
+pub const PREFERENCE: [Compression; 5] = [
+  Compression::Xz,
+  Compression::Zstd,
+  Compression::Gzip,
+  Compression::Lz4,
+  Compression::None,
+];
+
+// variants probed when no `Release` lists indices, which almost all repositories serve.
+pub const PROBED: [Compression; 3] = [Compression::Xz, Compression::Gzip, Compression::None];
+
+impl Compression {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      Self::Xz => ".xz",
+      Self::Zstd => ".zst",
+      Self::Gzip => ".gz",
+      Self::Lz4 => ".lz4",
+      Self::None => "",
+    }
+  }
+
+  // decode `bytes` of `target` into text.
+  pub fn decode(&self, bytes: &[u8], target: &str) -> Result<String, DownloadError> {
+    let failed = |reason: String| DownloadError::DecodeFailed {
+      target: target.to_string(),
+      reason,
+    };
+    let mut decoded = vec![];
+    match self {
+      Self::Xz => lzma_rs::xz_decompress(&mut &bytes[..], &mut decoded)
+        .map_err(|err| failed(err.to_string()))?,
+      Self::Zstd => {
+        ruzstd::streaming_decoder::StreamingDecoder::new(bytes)
+          .map_err(|err| failed(err.to_string()))?
+          .read_to_end(&mut decoded)
+          .map_err(|err| failed(err.to_string()))?;
+      }
+      Self::Gzip => {
+        GzDecoder::new(bytes)
+          .read_to_end(&mut decoded)
+          .map_err(|err| failed(err.to_string()))?;
+      }
+      Self::Lz4 => {
+        lz4_flex::frame::FrameDecoder::new(bytes)
+          .read_to_end(&mut decoded)
+          .map_err(|err| failed(err.to_string()))?;
+      }
+      Self::None => decoded.extend_from_slice(bytes),
+    };
+    String::from_utf8(decoded).map_err(|err| failed(err.to_string()))
+  }
+}
+
+// variants of the index at `path` (relative to the dist, without extension) listed in `release`,
+// in the order of preference.
+pub fn listed_variants(release: &Release, path: &str) -> Vec<(Compression, ReleaseFile)> {
+  PREFERENCE
+    .iter()
+    .filter_map(|compression| {
+      release
+        .file(&format!("{}{}", path, compression.extension()))
+        .map(|file| (*compression, file.clone()))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  #[test]
+  fn test_decode_variants() {
+    let base = "./tests/resources/index/Packages";
+    let plain = fs::read_to_string(base).unwrap();
+    for compression in PREFERENCE {
+      let path = format!("{}{}", base, compression.extension());
+      let bytes = fs::read(&path).unwrap();
+      assert_eq!(compression.decode(&bytes, &path).unwrap(), plain);
+    }
+
+    // broken file
+    assert!(matches!(
+      Compression::Xz.decode(plain.as_bytes(), base),
+      Err(DownloadError::DecodeFailed { .. })
+    ));
+  }
+
+  #[test]
+  fn test_listed_variants() {
+    let release = Release::from(
+      "SHA256:
+ e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 0 main/binary-amd64/Packages
+ 2f5c2a1ee9f3c3c7a3c4a1e3a1b5b3b0c3f0b1f5a8c5d6e6f2a3b4c5d6e7f8a9 12 main/binary-amd64/Packages.gz
+ 3f5c2a1ee9f3c3c7a3c4a1e3a1b5b3b0c3f0b1f5a8c5d6e6f2a3b4c5d6e7f8a9 10 main/binary-amd64/Packages.xz
+",
+    );
+    let variants: Vec<Compression> = listed_variants(&release, "main/binary-amd64/Packages")
+      .into_iter()
+      .map(|(compression, _)| compression)
+      .collect();
+    assert_eq!(
+      variants,
+      vec![Compression::Xz, Compression::Gzip, Compression::None]
+    );
+    assert!(listed_variants(&release, "main/binary-i386/Packages").is_empty());
+  }
+}
@@ -0,0 +1,324 @@
+/*
+ This file defines network configuration: proxies, timeouts, retries and user-agent.
+ They are read from apt.conf and environment variables as below:
+   - proxy: `Acquire::{http,https}::Proxy`, or `http_proxy`/`https_proxy` env if not set.
+            `https` falls back to the proxy of `http` if not given.
+   - per-host proxy: `Acquire::{http,https}::Proxy::<host>`, where `DIRECT` means no proxy.
+   - `no_proxy` env: comma-separated hosts and domains which never use proxies.
+   - `Acquire::http::ConnectTimeout`, `Acquire::http::Timeout`: in seconds, `0` means no limit.
+     Unlike apt, which regards `Timeout` as idle time of a connection, it limits the whole request
+     including its body, cuz the HTTP client has no idle timeout. So it's unlimited by default
+     not to abort large downloads on slow links.
+   - `Acquire::Retries`: number of retries on the same mirror.
+   - `Acquire::http::User-Agent`
+ apt.conf wins over environment variables as apt does.
+*/
+
+use super::mirror::RetryPolicy;
+use crate::apt::config::{AptConfig, ConfigError};
+
+use reqwest::Url;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+pub const DEFAULT_USER_AGENT: &str = concat!("rapt2/", env!("CARGO_PKG_VERSION"));
+
+// proxies of a scheme.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProxyConfig {
+  pub default: Option<String>,
+  pub hosts: HashMap<String, Option<String>>, // host => proxy, `None` for `DIRECT`
+}
+
+impl ProxyConfig {
+  fn proxy_for(&self, host: &str) -> Option<String> {
+    match self.hosts.get(host) {
+      Some(proxy) => proxy.clone(),
+      None => self.default.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetConfig {
+  pub http: ProxyConfig,
+  pub https: ProxyConfig,
+  pub no_proxy: Vec<String>,
+  pub connect_timeout: Option<Duration>,
+  pub timeout: Option<Duration>, // timeout of each request, including receiving its body
+  pub retry_policy: RetryPolicy,
+  pub user_agent: String,
+}
+
+impl Default for NetConfig {
+  fn default() -> Self {
+    Self {
+      http: ProxyConfig::default(),
+      https: ProxyConfig::default(),
+      no_proxy: vec![],
+      connect_timeout: None,
+      timeout: None,
+      retry_policy: RetryPolicy::default(),
+      user_agent: DEFAULT_USER_AGENT.into(),
+    }
+  }
+}
+
+impl NetConfig {
+  // read apt.conf in `source_dir` and environment variables.
+  pub fn read(source_dir: &Path) -> Result<Self, ConfigError> {
+    Self::new(&AptConfig::read(source_dir)?, |name| {
+      std::env::var(name).ok()
+    })
+  }
+
+  // build config from apt.conf and environment variables given by `env`.
+  pub fn new<E>(apt: &AptConfig, env: E) -> Result<Self, ConfigError>
+  where
+    E: Fn(&str) -> Option<String>,
+  {
+    let mut config = Self::default();
+    // lowercase variables are preferred, but `HTTP_PROXY` is never used as curl does.
+    let env = |name: &str| {
+      env(name)
+        .or_else(|| match name {
+          "http_proxy" => None,
+          _ => env(&name.to_uppercase()),
+        })
+        .filter(|value| !value.is_empty())
+    };
+
+    let http_proxy = apt
+      .get("Acquire::http::Proxy")
+      .map(String::from)
+      .or_else(|| env("http_proxy"));
+    let https_proxy = apt
+      .get("Acquire::https::Proxy")
+      .map(String::from)
+      .or_else(|| env("https_proxy"))
+      .or_else(|| http_proxy.clone());
+    config.http = read_proxies(apt, "http", http_proxy)?;
+    config.https = read_proxies(apt, "https", https_proxy)?;
+    if let Some(no_proxy) = env("no_proxy") {
+      config.no_proxy = no_proxy
+        .split(',')
+        .map(|host| host.trim().to_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect();
+    }
+
+    if let Some(secs) = read_number(apt, "Acquire::http::ConnectTimeout")? {
+      config.connect_timeout = seconds(secs);
+    }
+    if let Some(secs) = read_number(apt, "Acquire::http::Timeout")? {
+      config.timeout = seconds(secs);
+    }
+    if let Some(retries) = read_number(apt, "Acquire::Retries")? {
+      config.retry_policy.retries = retries as u32;
+    }
+    if let Some(user_agent) = apt.get("Acquire::http::User-Agent") {
+      config.user_agent = user_agent.into();
+    }
+
+    Ok(config)
+  }
+
+  // proxy URL to request `url` through, or `None` to connect directly.
+  pub fn proxy_for(&self, url: &Url) -> Option<String> {
+    let host = url.host_str()?.to_lowercase();
+    if self
+      .no_proxy
+      .iter()
+      .any(|pattern| matches_no_proxy(pattern, &host))
+    {
+      return None;
+    }
+    match url.scheme() {
+      "http" => self.http.proxy_for(&host),
+      "https" => self.https.proxy_for(&host),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for NetConfig {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let or_none = |proxy: &Option<String>| proxy.clone().unwrap_or_else(|| "(none)".into());
+    let or_unlimited = |timeout: Option<Duration>| match timeout {
+      Some(timeout) => format!("{}s", timeout.as_secs()),
+      None => "(unlimited)".into(),
+    };
+    writeln!(f, "http proxy: {}", or_none(&self.http.default))?;
+    writeln!(f, "https proxy: {}", or_none(&self.https.default))?;
+    for (scheme, proxies) in [("http", &self.http), ("https", &self.https)] {
+      let mut hosts: Vec<_> = proxies.hosts.iter().collect();
+      hosts.sort();
+      for (host, proxy) in hosts {
+        let proxy = proxy.clone().unwrap_or_else(|| "DIRECT".into());
+        writeln!(f, "{} proxy for {}: {}", scheme, host, proxy)?;
+      }
+    }
+    if !self.no_proxy.is_empty() {
+      writeln!(f, "no proxy: {}", self.no_proxy.join(","))?;
+    }
+    writeln!(f, "connect timeout: {}", or_unlimited(self.connect_timeout))?;
+    writeln!(f, "timeout: {}", or_unlimited(self.timeout))?;
+    writeln!(f, "retries: {}", self.retry_policy.retries)?;
+    write!(f, "user-agent: {}", self.user_agent)
+  }
+}
+
+// per-host proxies of `scheme` in apt.conf, and the proxy for other hosts.
+fn read_proxies(
+  apt: &AptConfig,
+  scheme: &str,
+  default: Option<String>,
+) -> Result<ProxyConfig, ConfigError> {
+  let key = format!("Acquire::{}::Proxy", scheme);
+  let mut proxies = ProxyConfig {
+    default,
+    hosts: HashMap::new(),
+  };
+  for (host, proxy) in apt.children(&key) {
+    proxies.hosts.insert(host, Some(proxy));
+  }
+
+  // `DIRECT` and empty value mean no proxy.
+  let check = |proxy: Option<String>| -> Result<Option<String>, ConfigError> {
+    match proxy {
+      Some(proxy) if proxy.eq_ignore_ascii_case("DIRECT") || proxy.is_empty() => Ok(None),
+      Some(proxy) => match Url::parse(&proxy) {
+        Ok(_) => Ok(Some(proxy)),
+        Err(_) => Err(ConfigError::InvalidValue {
+          key: key.clone(),
+          value: proxy,
+        }),
+      },
+      None => Ok(None),
+    }
+  };
+  proxies.default = check(proxies.default)?;
+  for proxy in proxies.hosts.values_mut() {
+    *proxy = check(proxy.take())?;
+  }
+  Ok(proxies)
+}
+
+fn read_number(apt: &AptConfig, key: &str) -> Result<Option<u64>, ConfigError> {
+  match apt.get(key) {
+    Some(value) => value
+      .trim()
+      .parse()
+      .map(Some)
+      .map_err(|_| ConfigError::InvalidValue {
+        key: key.into(),
+        value: value.into(),
+      }),
+    None => Ok(None),
+  }
+}
+
+fn seconds(secs: u64) -> Option<Duration> {
+  match secs {
+    0 => None,
+    secs => Some(Duration::from_secs(secs)),
+  }
+}
+
+// `*` matches every host, and `example.com` or `.example.com` matches the domain and its subdomains.
+fn matches_no_proxy(pattern: &str, host: &str) -> bool {
+  let domain = pattern.trim_start_matches('.');
+  pattern == "*" || host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn url(s: &str) -> Url {
+    Url::parse(s).unwrap()
+  }
+
+  #[test]
+  fn test_net_config() {
+    let apt = AptConfig::from(
+      r#"
+Acquire::http::Proxy "http://proxy.example.com:3128";
+Acquire::http::Proxy::mirror.example.com "DIRECT";
+Acquire::https::Proxy::secure.example.com "http://secure-proxy:8080";
+Acquire::http::ConnectTimeout "5";
+Acquire::http::Timeout "0";
+Acquire::Retries "4";
+Acquire::http::User-Agent "rapt2-ci";
+"#,
+    )
+    .unwrap();
+    let config = NetConfig::new(&apt, |name| match name {
+      "NO_PROXY" => Some("localhost, .internal".into()),
+      _ => None,
+    })
+    .unwrap();
+
+    assert_eq!(
+      config.proxy_for(&url("http://archive.ubuntu.com/ubuntu")),
+      Some("http://proxy.example.com:3128".into())
+    );
+    // `https` falls back to the proxy of `http`.
+    assert_eq!(
+      config.proxy_for(&url("https://archive.ubuntu.com/ubuntu")),
+      Some("http://proxy.example.com:3128".into())
+    );
+    assert_eq!(
+      config.proxy_for(&url("https://secure.example.com/")),
+      Some("http://secure-proxy:8080".into())
+    );
+    assert_eq!(config.proxy_for(&url("http://mirror.example.com/")), None);
+    assert_eq!(config.proxy_for(&url("http://localhost:8080/")), None);
+    assert_eq!(config.proxy_for(&url("http://repo.internal/")), None);
+    assert_eq!(config.connect_timeout, Some(Duration::from_secs(5)));
+    assert_eq!(config.timeout, None);
+    // large downloads on slow links are never aborted by default.
+    assert_eq!(NetConfig::default().timeout, None);
+    assert_eq!(config.retry_policy.retries, 4);
+    assert_eq!(config.user_agent, "rapt2-ci");
+
+    // environment variables are used only if apt.conf doesn't set proxies.
+    let env = |name: &str| match name {
+      "http_proxy" => Some("http://env-proxy:3128".into()),
+      "HTTPS_PROXY" => Some("DIRECT".into()),
+      _ => None,
+    };
+    let config = NetConfig::new(&apt, env).unwrap();
+    assert_eq!(
+      config.http.default,
+      Some("http://proxy.example.com:3128".into())
+    );
+    // `https_proxy` env precedes the fallback to the proxy of `http`.
+    assert_eq!(config.https.default, None);
+    let config = NetConfig::new(&AptConfig::default(), env).unwrap();
+    assert_eq!(config.http.default, Some("http://env-proxy:3128".into()));
+    assert_eq!(config.https.default, None);
+
+    // `HTTP_PROXY` is never used.
+    let config = NetConfig::new(&AptConfig::default(), |name| match name {
+      "HTTP_PROXY" => Some("http://env-proxy:3128".into()),
+      _ => None,
+    })
+    .unwrap();
+    assert_eq!(config, NetConfig::default());
+  }
+
+  #[test]
+  fn test_invalid_net_config() {
+    let invalid = |content: &str| {
+      let apt = AptConfig::from(content).unwrap();
+      NetConfig::new(&apt, |_| None).is_err()
+    };
+    assert!(invalid("Acquire::http::Proxy \"proxy.example.com\";"));
+    assert!(invalid("Acquire::Retries \"many\";"));
+    assert!(invalid("Acquire::http::Timeout \"-1\";"));
+    assert!(!invalid("Acquire::http::Proxy \"DIRECT\";"));
+  }
+}
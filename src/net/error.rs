@@ -20,6 +20,38 @@ pub enum DownloadError {
   #[error("file/dir for caching not found: {name:?}")]
   FileNotFound { name: String },
 
+  #[error("invalid signature of InRelease of {target}: {reason}")]
+  InvalidSignature { target: String, reason: String },
+
+  #[error("failed to read keyring {path}: {msg}")]
+  InvalidKeyring { path: String, msg: String },
+
+  #[error("size mismatch of {target}: expected {expected}, but got {actual}")]
+  SizeMismatch {
+    target: String,
+    expected: u64,
+    actual: u64,
+  },
+
+  #[error("SHA256 mismatch of {target}: expected {expected}, but got {actual}")]
+  HashMismatch {
+    target: String,
+    expected: String,
+    actual: String,
+  },
+
+  #[error("local file {path} is not found")]
+  LocalFileNotFound { path: String },
+
+  #[error("{target} is not listed in InRelease")]
+  IndexNotListed { target: String },
+
+  #[error("failed to decode {target}: {reason}")]
+  DecodeFailed { target: String, reason: String },
+
+  #[error("downloaded archive {filename} is broken: {reason}")]
+  InvalidArchive { filename: String, reason: String },
+
   #[error("package IO error")]
   PackageIOError(#[from] crate::package::error::PackageError),
 }
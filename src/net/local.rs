@@ -0,0 +1,124 @@
+/*
+ This file implements local transport of `file:` and `copy:` URIs, which serve on-disk repositories.
+ apt uses archives of `file:` in place, but rapt2 copies them into archive dir as `copy:`,
+ cuz dpkg is invoked with archives in it.
+*/
+
+use super::error::DownloadError;
+use super::transport::{Metadata, RangeBody, Transport};
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+const SCHEMES: [&str; 2] = ["file:", "copy:"];
+
+// path on disk of `url` if it's a local URI, eg) `file:/srv/repo` or `file:///srv/repo`.
+pub fn local_path(url: &str) -> Option<PathBuf> {
+  let path = SCHEMES.iter().find_map(|scheme| url.strip_prefix(scheme))?;
+  // `file://localhost/srv/repo` is the same as `file:/srv/repo`.
+  let path = match path.strip_prefix("//") {
+    Some(rest) => rest.strip_prefix("localhost").unwrap_or(rest),
+    None => path,
+  };
+  Some(PathBuf::from(path))
+}
+
+pub fn is_local(url: &str) -> bool {
+  local_path(url).is_some()
+}
+
+fn read(url: &str) -> Result<Vec<u8>, DownloadError> {
+  let path = local_path(url).ok_or_else(|| not_found(url))?;
+  fs::read(&path).map_err(|err| match err.kind() {
+    ErrorKind::NotFound => not_found(url),
+    _ => DownloadError::FileIOError(err),
+  })
+}
+
+// transport of local repositories.
+#[derive(Debug, Default)]
+pub struct FileTransport;
+
+impl Transport for FileTransport {
+  // last modified time of the file is used instead of `If-Modified-Since`.
+  fn fetch_if_modified(
+    &self,
+    url: &str,
+    since: Option<SystemTime>,
+  ) -> Result<Option<Vec<u8>>, DownloadError> {
+    if let (Some(since), Some(modified)) = (since, self.metadata(url)?.modified) {
+      if modified <= since {
+        return Ok(None);
+      }
+    }
+    read(url).map(Some)
+  }
+
+  fn fetch_range(&self, url: &str, start: u64) -> Result<RangeBody, DownloadError> {
+    let bytes = read(url)?;
+    if start != 0 && start < bytes.len() as u64 {
+      Ok(RangeBody::Partial(bytes[start as usize..].to_vec()))
+    } else {
+      Ok(RangeBody::Whole(bytes))
+    }
+  }
+
+  fn metadata(&self, url: &str) -> Result<Metadata, DownloadError> {
+    let path = local_path(url).ok_or_else(|| not_found(url))?;
+    let meta = fs::metadata(&path).map_err(|_| not_found(url))?;
+    Ok(Metadata {
+      size: Some(meta.len()),
+      modified: meta.modified().ok(),
+    })
+  }
+}
+
+fn not_found(url: &str) -> DownloadError {
+  DownloadError::LocalFileNotFound {
+    path: url.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_local_path() {
+    assert_eq!(
+      local_path("file:/srv/repo/./Packages"),
+      Some(PathBuf::from("/srv/repo/./Packages"))
+    );
+    assert_eq!(
+      local_path("file:///srv/repo"),
+      Some(PathBuf::from("/srv/repo"))
+    );
+    assert_eq!(
+      local_path("file://localhost/srv/repo"),
+      Some(PathBuf::from("/srv/repo"))
+    );
+    assert_eq!(
+      local_path("copy:/srv/repo"),
+      Some(PathBuf::from("/srv/repo"))
+    );
+    assert_eq!(local_path("http://example.com/ubuntu"), None);
+    assert!(!is_local("https://example.com/ubuntu"));
+  }
+
+  #[test]
+  fn test_read_local() {
+    let path = std::fs::canonicalize("./tests/resources/index/Packages").unwrap();
+    let url = format!("file:{}", path.to_string_lossy());
+    assert_eq!(read(&url).unwrap(), fs::read(&path).unwrap());
+    assert!(FileTransport.metadata(&url).is_ok());
+
+    let url = format!("copy:{}.missing", path.to_string_lossy());
+    assert!(matches!(
+      read(&url),
+      Err(DownloadError::LocalFileNotFound { .. })
+    ));
+    assert!(FileTransport.metadata(&url).is_err());
+  }
+}
@@ -0,0 +1,167 @@
+/*
+ This file implements retry with backoff and failover among mirrors of a source.
+*/
+
+use super::error::DownloadError;
+use crate::source::source::Source;
+
+use reqwest::StatusCode;
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_RETRIES: u32 = 2;
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+  pub retries: u32,      // number of retries on the same mirror
+  pub backoff: Duration, // wait before the first retry, which is doubled for each retry
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      retries: DEFAULT_RETRIES,
+      backoff: DEFAULT_BACKOFF,
+    }
+  }
+}
+
+// how to handle an error of a mirror.
+#[derive(Debug, PartialEq, Eq)]
+enum Recovery {
+  Retry,    // temporary error, which may be recovered on the same mirror
+  Failover, // the mirror is broken or outdated, so try next mirror
+  Abort,    // error not related to the mirror
+}
+
+fn recovery_of(err: &DownloadError) -> Recovery {
+  match err {
+    DownloadError::RequestFailed(_) => Recovery::Retry,
+    DownloadError::InvalidStatusCode { status } if status.is_server_error() => Recovery::Retry,
+    DownloadError::InvalidStatusCode { status } if *status == StatusCode::TOO_MANY_REQUESTS => {
+      Recovery::Retry
+    }
+    DownloadError::InvalidStatusCode { .. }
+    | DownloadError::SizeMismatch { .. }
+    | DownloadError::HashMismatch { .. }
+    | DownloadError::DecodeFailed { .. }
+    | DownloadError::LocalFileNotFound { .. }
+    | DownloadError::InvalidArchive { .. } => Recovery::Failover,
+    _ => Recovery::Abort,
+  }
+}
+
+// call `f` with `source` served by each mirror til it succeeds.
+// Temporary errors are retried with backoff before falling over to the next mirror.
+// If all mirrors fail, the error of the last mirror is returned.
+pub fn with_failover<R, F>(source: &Source, policy: &RetryPolicy, f: F) -> Result<R, DownloadError>
+where
+  F: Fn(&Source) -> Result<R, DownloadError>,
+{
+  let mut last_err = None;
+  for url in source.base_urls() {
+    let mirror = source.mirrored(&url);
+    let mut backoff = policy.backoff;
+    let mut attempt = 0;
+    loop {
+      let err = match f(&mirror) {
+        Ok(result) => return Ok(result),
+        Err(err) => err,
+      };
+      match recovery_of(&err) {
+        Recovery::Retry if attempt < policy.retries => {
+          attempt += 1;
+          thread::sleep(backoff);
+          backoff *= 2;
+        }
+        Recovery::Retry | Recovery::Failover => {
+          last_err = Some(err);
+          break;
+        }
+        Recovery::Abort => return Err(err),
+      }
+    }
+  }
+  Err(last_err.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dpkg::arch::native_arch;
+  use crate::source::source::{ArchivedType, Component};
+  use std::cell::RefCell;
+
+  #[test]
+  fn test_failover() {
+    let source = Source {
+      archive_type: ArchivedType::DEB,
+      url: "http://primary/".into(),
+      distro: "focal".into(),
+      component: Component::MAIN,
+      options: Default::default(),
+      mirrors: vec!["http://broken/".into(), "http://fallback/".into()],
+    };
+    let policy = RetryPolicy {
+      retries: 2,
+      backoff: Duration::from_millis(0),
+    };
+    let tried = RefCell::new(vec![]);
+
+    // primary is temporarily unavailable, and first fallback has broken index.
+    let result = with_failover(&source, &policy, |mirror| {
+      tried.borrow_mut().push(mirror.url.clone());
+      match mirror.url.as_str() {
+        "http://primary/" => Err(DownloadError::InvalidStatusCode {
+          status: StatusCode::SERVICE_UNAVAILABLE,
+        }),
+        "http://broken/" => Err(DownloadError::HashMismatch {
+          target: mirror.packages_url(),
+          expected: "a".into(),
+          actual: "b".into(),
+        }),
+        _ => Ok(mirror.packages_url()),
+      }
+    });
+    assert_eq!(
+      result.unwrap(),
+      format!(
+        "http://fallback/dists/focal/main/binary-{}/Packages",
+        native_arch()
+      )
+    );
+    assert_eq!(
+      *tried.borrow(),
+      vec![
+        "http://primary/",
+        "http://primary/",
+        "http://primary/",
+        "http://broken/",
+        "http://fallback/"
+      ]
+    );
+
+    // errors not related to mirrors are never retried.
+    tried.borrow_mut().clear();
+    let result: Result<(), DownloadError> = with_failover(&source, &policy, |mirror| {
+      tried.borrow_mut().push(mirror.url.clone());
+      Err(DownloadError::FileNotFound {
+        name: "hoge".into(),
+      })
+    });
+    assert!(matches!(result, Err(DownloadError::FileNotFound { .. })));
+    assert_eq!(tried.borrow().len(), 1);
+
+    // 404 on all mirrors
+    let result: Result<(), DownloadError> = with_failover(&source, &policy, |_| {
+      Err(DownloadError::InvalidStatusCode {
+        status: StatusCode::NOT_FOUND,
+      })
+    });
+    assert!(matches!(
+      result,
+      Err(DownloadError::InvalidStatusCode { .. })
+    ));
+  }
+}
@@ -0,0 +1,93 @@
+/*
+ This file implements in-memory transport, which serves files put by tests instead of mirrors.
+*/
+
+use super::error::DownloadError;
+use super::transport::{Metadata, RangeBody, Transport};
+
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+#[derive(Debug, Default)]
+pub struct MockTransport {
+  files: Mutex<HashMap<String, (Vec<u8>, SystemTime)>>, // URL => content and its last modified time
+  requests: Mutex<Vec<String>>,                         // URLs requested so far, in order
+}
+
+impl MockTransport {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // serve `content` at `url`, which is regarded as modified now.
+  pub fn put(&self, url: &str, content: impl Into<Vec<u8>>) {
+    self.put_modified(url, content, SystemTime::now());
+  }
+
+  pub fn put_modified(&self, url: &str, content: impl Into<Vec<u8>>, modified: SystemTime) {
+    self
+      .files
+      .lock()
+      .unwrap()
+      .insert(url.to_string(), (content.into(), modified));
+  }
+
+  pub fn remove(&self, url: &str) {
+    self.files.lock().unwrap().remove(url);
+  }
+
+  pub fn requests(&self) -> Vec<String> {
+    self.requests.lock().unwrap().clone()
+  }
+
+  pub fn clear_requests(&self) {
+    self.requests.lock().unwrap().clear();
+  }
+
+  // record the request, and get the file as mirrors respond 404 for missing files.
+  fn get(&self, url: &str) -> Result<(Vec<u8>, SystemTime), DownloadError> {
+    self.requests.lock().unwrap().push(url.to_string());
+    self
+      .files
+      .lock()
+      .unwrap()
+      .get(url)
+      .cloned()
+      .ok_or(DownloadError::InvalidStatusCode {
+        status: StatusCode::NOT_FOUND,
+      })
+  }
+}
+
+impl Transport for MockTransport {
+  fn fetch_if_modified(
+    &self,
+    url: &str,
+    since: Option<SystemTime>,
+  ) -> Result<Option<Vec<u8>>, DownloadError> {
+    let (content, modified) = self.get(url)?;
+    match since {
+      Some(since) if modified <= since => Ok(None),
+      _ => Ok(Some(content)),
+    }
+  }
+
+  fn fetch_range(&self, url: &str, start: u64) -> Result<RangeBody, DownloadError> {
+    let (content, _) = self.get(url)?;
+    if start != 0 && start < content.len() as u64 {
+      Ok(RangeBody::Partial(content[start as usize..].to_vec()))
+    } else {
+      Ok(RangeBody::Whole(content))
+    }
+  }
+
+  fn metadata(&self, url: &str) -> Result<Metadata, DownloadError> {
+    let (content, modified) = self.get(url)?;
+    Ok(Metadata {
+      size: Some(content.len() as u64),
+      modified: Some(modified),
+    })
+  }
+}
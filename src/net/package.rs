@@ -3,15 +3,19 @@
  For caching strategy, refer to /docs/caching.md
 */
 
+use super::checksum::{sha256_hex, verify_checksum};
+use super::compression::{listed_variants, Compression, PROBED};
 use super::error::DownloadError;
+use super::mirror::{with_failover, RetryPolicy};
+use super::pdiff::{apply_ed, DiffIndex};
+use super::pool::DownloadPool;
+use super::signature::{verify_inrelease, Keyring};
+use super::transport::Transport;
+use crate::source::release::{Release, ReleaseFile};
 use crate::source::source::*;
-use crate::util::*;
 
-use flate2::read::GzDecoder;
-use reqwest::header::IF_MODIFIED_SINCE;
 use reqwest::StatusCode;
 use std::fs;
-use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
@@ -19,19 +23,22 @@ use std::time::SystemTime;
 pub struct PackageDownloadClient {
   source_infos: Vec<SourceInfo>,
   cache_dir: PathBuf,
-  cur_inrelease: usize, // index of SourceInfo which is checked for `InRelease`
-  cur_packages: usize,  // index of SourceInfo which is checked for `Packages`
+  trusted_keyring: Keyring, // keyrings in `trusted.gpg(.d)`
 }
 
 #[derive(Debug)]
 struct SourceInfo {
   source: Source,
-  old_package_hash: Option<String>,
+  old_package_hash: Option<String>, // SHA256 of `Packages` in cached `InRelease`
   should_update: Option<bool>,
 }
 
 impl PackageDownloadClient {
-  pub fn new(sources: Vec<Source>, cache_dir: PathBuf) -> Result<Self, DownloadError> {
+  pub fn new(
+    sources: Vec<Source>,
+    cache_dir: PathBuf,
+    source_dir: &Path,
+  ) -> Result<Self, DownloadError> {
     let mut source_infos: Vec<SourceInfo> = vec![];
 
     // check existence of cache dir
@@ -53,11 +60,12 @@ impl PackageDownloadClient {
       }
     }
 
-    // read `InRelease` caches and associate old MD5Sum hash with each source.
+    // read `InRelease` caches and associate old SHA256 hash with each source.
     for group in source_groups {
       let representative_source = &group[0];
-      let inrelease = match read_inrelease_cache(representative_source, &cache_dir) {
-        Some(inrelease) => inrelease,
+      let release = match Release::read(&cache_dir.join(representative_source.inrelease_filename()))
+      {
+        Some(release) => release,
         None => {
           for source in group {
             source_infos.push(SourceInfo {
@@ -70,11 +78,10 @@ impl PackageDownloadClient {
         }
       };
       for source in group {
-        let search_string = format!("{}/binary-amd64/Packages.gz", source.component);
-        let md5 = search_md5(&search_string, &inrelease);
+        let sha256 = index_hash(&release, &source);
         source_infos.push(SourceInfo {
           source,
-          old_package_hash: md5,
+          old_package_hash: sha256,
           should_update: None,
         });
       }
@@ -83,117 +90,230 @@ impl PackageDownloadClient {
     Ok(Self {
       source_infos,
       cache_dir,
-      cur_inrelease: 0,
-      cur_packages: 0,
+      trusted_keyring: Keyring::read_trusted(source_dir),
     })
   }
 
-  pub fn get_done_packages_num(&mut self) -> usize {
-    self.cur_packages
-  }
-
-  pub fn get_done_inrelease_num(&mut self) -> usize {
-    self.increment_inrelease_counter();
-    self.cur_inrelease
-  }
-
-  fn increment_inrelease_counter(&mut self) {
-    while self.cur_inrelease < self.source_infos.len() {
-      if self.source_infos[self.cur_inrelease]
-        .should_update
-        .is_none()
+  // Get `InRelease` of each dist in parallel and update each sources should download `Packages` files.
+  pub fn fetch_all_inrelease(&mut self, pool: &DownloadPool) -> Result<(), DownloadError> {
+    // `InRelease` is shared by sources with same `url` and `distro`.
+    let mut targets: Vec<Source> = vec![];
+    for info in &self.source_infos {
+      if !targets
+        .iter()
+        .any(|target| target.url == info.source.url && target.distro == info.source.distro)
       {
-        break;
+        targets.push(info.source.clone());
       }
-      self.cur_inrelease += 1;
     }
-  }
 
-  pub fn get_next_target_source_packages(&mut self) -> Option<Source> {
-    if self.cur_packages >= self.source_infos.len() {
-      None
-    } else {
-      Some(self.source_infos[self.cur_packages].source.clone())
+    let results = pool.run(
+      &targets,
+      |source| (source.inrelease_url(), source.inrelease_url()),
+      |transport, source| self.get_inrelease(transport, pool.retry_policy(), source),
+    );
+
+    // check `InRelease` file and update whether each source should download `Packages`.
+    for (source, result) in targets.iter().zip(results) {
+      let result = match result {
+        // Release-only, `Release.gpg`-only and unsigned flat repositories serve no `InRelease`,
+        // so indices of trusted sources are probed without it.
+        Err(err) if source.is_trusted() && is_not_found(&err) => {
+          self.forget_inrelease(source)?;
+          continue;
+        }
+        result => result?,
+      };
+      match result {
+        Some(inrelease) => {
+          // never save unverified `InRelease`, which is trusted as cache after that.
+          // only the signed text is kept, so unsigned lines around it are never parsed.
+          let inrelease = self.verify_inrelease(&inrelease, source)?;
+          self.update_should_download_packages(Some(&inrelease), source);
+          self.save_cache_inrelease(&inrelease, source)?;
+        }
+        None => self.update_should_download_packages(None, source),
+      };
     }
-  }
 
-  pub fn get_next_target_source_inrelease(&mut self) -> Option<Source> {
-    // increment `self.cur_inrelease` til finding not-check Source.
-    self.increment_inrelease_counter();
+    // skip architectures which are not built in the dist, such as `i386` of recent Ubuntu.
+    // Flat repository has a single index for all architectures, so it's always kept.
+    let cache_dir = &self.cache_dir;
+    self.source_infos.retain(|info| {
+      if info.source.is_flat() {
+        return true;
+      }
+      match Release::read(&cache_dir.join(info.source.inrelease_filename())) {
+        Some(release) => release.supports_arch(info.source.arch()),
+        None => true,
+      }
+    });
+
+    Ok(())
+  }
 
-    if self.cur_inrelease >= self.source_infos.len() {
-      None
-    } else {
-      Some(self.source_infos[self.cur_inrelease].source.clone())
+  // Get `Packages` of each source in parallel, or read local DB cache if there is no need to download.
+  // Contents are returned in the same order as sources.
+  pub fn fetch_all_packages(&self, pool: &DownloadPool) -> Result<Vec<String>, DownloadError> {
+    let targets: Vec<&SourceInfo> = self
+      .source_infos
+      .iter()
+      .filter(|info| info.should_update.unwrap_or(true))
+      .collect();
+    let mut downloaded = pool
+      .run(
+        &targets,
+        |info| (info.source.packages_url(), info.source.packages_url()),
+        |transport, info| self.get_packages(transport, pool.retry_policy(), &info.source),
+      )
+      .into_iter();
+
+    let mut contents = vec![];
+    for info in &self.source_infos {
+      if info.should_update.unwrap_or(true) {
+        let body = downloaded.next().unwrap()?;
+        self.save_cache_packages(&body, &info.source)?;
+        contents.push(body);
+      } else {
+        let package_client = crate::package::client::PackageClient::new(self.cache_dir.clone())?;
+        contents.push(package_client.read_single_file_raw(&info.source.cache_filename())?);
+      }
     }
+
+    Ok(contents)
   }
 
-  // Get `InRelease` and update each sources should download `Packages` files.
-  // If all downloads are complete, returns Ok(None).
-  pub fn get_package_ifneed(&mut self) -> Result<Option<String>, DownloadError> {
-    if self.cur_packages >= self.source_infos.len() {
-      return Ok(None);
+  // Actual download of `Packages`, which is verified by `SHA256` of `InRelease`.
+  // Cached `Packages` is patched by PDiff if possible, otherwise full `Packages` is downloaded.
+  fn get_packages(
+    &self,
+    transport: &dyn Transport,
+    policy: &RetryPolicy,
+    source: &Source,
+  ) -> Result<String, DownloadError> {
+    // `InRelease` is already fetched and verified, so its `SHA256` is trusted.
+    let release = Release::read(&self.cache_dir.join(source.inrelease_filename()));
+    if let Some(release) = &release {
+      // any failure of PDiff, including hash mismatch of the patched one, falls back to full download.
+      if let Ok(Some(body)) = self.get_packages_by_pdiff(transport, policy, source, release) {
+        return Ok(body);
+      }
     }
 
-    // if there is no need to download `Packages`, read local DB cache.
-    let source = &self.source_infos[self.cur_packages].source;
-    let should_update = self.source_infos[self.cur_packages].should_update.unwrap();
-    self.cur_packages += 1;
-    if !should_update {
-      let package_client = crate::package::client::PackageClient::new(self.cache_dir.clone())?;
-      let packages_str = package_client.read_single_file_raw(&source.cache_filename())?;
-      return Ok(Some(packages_str));
+    let variants: Vec<(Compression, Option<ReleaseFile>)> = match &release {
+      Some(release) => listed_variants(release, &source.packages_index_path())
+        .into_iter()
+        .map(|(compression, file)| (compression, Some(file)))
+        .collect(),
+      // without file list, only trusted sources may be fetched without hashes.
+      None if source.is_trusted() => PROBED
+        .iter()
+        .map(|compression| (*compression, None))
+        .collect(),
+      None => vec![],
+    };
+    if variants.is_empty() {
+      return Err(DownloadError::IndexNotListed {
+        target: source.packages_url(),
+      });
     }
 
-    // Actual download of `Packages`.
-    let url = source.packages_url();
-    let client = reqwest::blocking::Client::builder()
-      .gzip(false)
-      .build()
-      .unwrap();
-    // XXX should add `If-Modified-Since` here also?
-    let result = client.get(url).send();
-    match result {
-      Ok(res) => {
-        let bytes: Vec<u8> = res.bytes().unwrap().into_iter().collect::<Vec<u8>>();
-        let mut decoder = GzDecoder::new(&bytes[..]);
-        let mut body = String::new();
-        decoder.read_to_string(&mut body)?;
-
-        // save cache
-        self.save_cache_packages(&body, source)?;
-        Ok(Some(body))
+    // try variants in the order of preference, cuz some mirrors lack a listed variant.
+    let mut last_err = None;
+    for (compression, expected) in variants {
+      let result = with_failover(source, policy, |mirror| {
+        let url = format!("{}{}", mirror.packages_url(), compression.extension());
+        // XXX should add `If-Modified-Since` here also?
+        let bytes = match &expected {
+          Some(expected) => fetch_verified(transport, &url, expected)?,
+          None => transport.fetch(&url)?,
+        };
+        compression.decode(&bytes, &url)
+      });
+      match result {
+        Ok(body) => return Ok(body),
+        Err(err) => last_err = Some(err),
       }
-      Err(err) => Err(DownloadError::RequestFailed(err)),
     }
+    Err(last_err.unwrap())
   }
 
-  // Get `InRelease` and update each sources should download `Packages` files.
-  // If check is complete, returns Ok(false).
-  // NOTE: Actually, I want to implement this as Iterator.
-  //      But changing Iter itself is not allowed for elements of Iter in Rust.
-  pub fn get_inrelease_ifneed(&mut self) -> Result<bool, DownloadError> {
-    // increment `self.cur_inrelease` til finding not-check Source.
-    self.increment_inrelease_counter();
-    if self.cur_inrelease >= self.source_infos.len() {
-      return Ok(false);
-    }
+  // Update cached `Packages` by applying patches listed in `Packages.diff/Index`.
+  // It returns `Ok(None)` if PDiff is not available, eg) no cache, or the cache is older than history.
+  fn get_packages_by_pdiff(
+    &self,
+    transport: &dyn Transport,
+    policy: &RetryPolicy,
+    source: &Source,
+    release: &Release,
+  ) -> Result<Option<String>, DownloadError> {
+    let expected_index = match release.file(&format!("{}.diff/Index", source.packages_index_path()))
+    {
+      Some(file) => file,
+      None => return Ok(None),
+    };
+    let mut content = match fs::read_to_string(self.cache_dir.join(source.cache_filename())) {
+      Ok(content) => content,
+      Err(_) => return Ok(None),
+    };
 
-    // fetch `InRelease` file
-    // and check `InRelease` file and update whether each source should download `Packages`.
-    let source = &self.source_infos[self.cur_inrelease].source.clone();
-    match self.get_inrelease(source)? {
-      Some(inrelease) => {
-        self.update_should_download_packages(Some(&inrelease), source);
-        self.save_cache_inrelease(&inrelease, source)?;
-      }
-      None => self.update_should_download_packages(None, source),
+    let index = with_failover(source, policy, |mirror| {
+      let url = format!("{}.diff/Index", mirror.packages_url());
+      let bytes = fetch_verified(transport, &url, expected_index)?;
+      Compression::None.decode(&bytes, &url)
+    })?;
+    let index = DiffIndex::from(&index);
+    let current = match &index.current {
+      Some(current) => current,
+      None => return Ok(None),
+    };
+    let names = match index.patches_for(&sha256_hex(content.as_bytes())) {
+      Some(names) => names,
+      None => return Ok(None),
     };
 
-    Ok(true)
+    for name in names {
+      let (download, patch) = match (index.downloads.get(&name), index.patches.get(&name)) {
+        (Some(download), Some(patch)) => (download, patch),
+        _ => return Ok(None),
+      };
+      let script = with_failover(source, policy, |mirror| {
+        let url = format!("{}.diff/{}.gz", mirror.packages_url(), name);
+        let bytes = fetch_verified(transport, &url, download)?;
+        let script = Compression::Gzip.decode(&bytes, &url)?;
+        verify_checksum(script.as_bytes(), patch.size, &patch.sha256, &url)?;
+        Ok(script)
+      })?;
+      content = apply_ed(&content, &script).map_err(|reason| DownloadError::DecodeFailed {
+        target: format!("{}.diff/{}", source.packages_url(), name),
+        reason,
+      })?;
+    }
+
+    // patched one must be identical to the latest `Packages`.
+    let target = source.packages_url();
+    verify_checksum(content.as_bytes(), current.size, &current.sha256, &target)?;
+    if let Some(expected) = release.file(&source.packages_index_path()) {
+      verify_checksum(content.as_bytes(), expected.size, &expected.sha256, &target)?;
+    }
+    Ok(Some(content))
   }
 
-  // update `self.should_update` status of SourceInfo by comparing new and old MD5 of Packages.
+  // verify signature of `InRelease` against `signed-by` keyring of the source if specified,
+  // otherwise against trusted keyrings.
+  // `InRelease` of sources marked as `trusted=yes` is not verified and returned as it is.
+  fn verify_inrelease(&self, inrelease: &str, source: &Source) -> Result<String, DownloadError> {
+    if source.is_trusted() {
+      return Ok(inrelease.to_string());
+    }
+    let target = source.inrelease_url();
+    match &source.options.signed_by {
+      Some(path) => verify_inrelease(inrelease, &Keyring::read(path)?, &target),
+      None => verify_inrelease(inrelease, &self.trusted_keyring, &target),
+    }
+  }
+
+  // update `self.should_update` status of SourceInfo by comparing new and old SHA256 of Packages.
   // if `inrelease` given is None, this func regards all associated sources are up-to-new.
   fn update_should_download_packages(&mut self, inrelease: Option<&str>, source: &Source) {
     let targets: Vec<&mut SourceInfo> = self
@@ -211,13 +331,13 @@ impl PackageDownloadClient {
     }
 
     if let Some(inrelease) = inrelease {
+      let release = Release::from(inrelease);
       for target in targets {
-        // check existing list DB's md5 hash
-        let target_str = format!("{}/binary-amd64/Packages.gz", target.source.component);
-        match search_md5(&target_str, inrelease) {
-          Some(md5) => {
+        // check existing list DB's SHA256 hash
+        match index_hash(&release, &target.source) {
+          Some(sha256) => {
             if target.old_package_hash.is_some()
-              && target.old_package_hash.as_ref().unwrap() == &md5
+              && target.old_package_hash.as_ref().unwrap() == &sha256
             {
               target.should_update = Some(false);
             } else {
@@ -237,27 +357,20 @@ impl PackageDownloadClient {
 
   // Get `InRelease` file with using cache.
   // If `InRelease` is not modified, it returns `Ok(None)`
-  fn get_inrelease(&self, source: &Source) -> Result<Option<String>, DownloadError> {
-    let url = source.inrelease_url();
-    let client = reqwest::blocking::Client::new();
-    let result = if let Some(timestamp) = self.check_existing_timestamp(source) {
-      client
-        .get(url)
-        .header(IF_MODIFIED_SINCE, timestamp2ims(timestamp))
-        .send()
-    } else {
-      client.get(url).send()
-    };
-    match result {
-      Ok(res) => match res.status() {
-        StatusCode::NOT_MODIFIED => Ok(None),
-        StatusCode::OK => Ok(Some(res.text().unwrap())),
-        _ => Err(DownloadError::InvalidStatusCode {
-          status: res.status(),
-        }),
-      },
-      Err(err) => Err(DownloadError::RequestFailed(err)),
-    }
+  fn get_inrelease(
+    &self,
+    transport: &dyn Transport,
+    policy: &RetryPolicy,
+    source: &Source,
+  ) -> Result<Option<String>, DownloadError> {
+    let timestamp = self.check_existing_timestamp(source);
+    with_failover(source, policy, |mirror| {
+      let url = mirror.inrelease_url();
+      match transport.fetch_if_modified(&url, timestamp)? {
+        Some(bytes) => Compression::None.decode(&bytes, &url).map(Some),
+        None => Ok(None),
+      }
+    })
   }
 
   // get timestamp of target source's `InRelease` file
@@ -273,6 +386,22 @@ impl PackageDownloadClient {
     }
   }
 
+  // drop cached `InRelease` of `source` which no longer serves it, and download its indices.
+  fn forget_inrelease(&mut self, source: &Source) -> Result<(), DownloadError> {
+    let path = self.cache_dir.join(source.inrelease_filename());
+    if path.is_file() {
+      fs::remove_file(path)?;
+    }
+    for info in self
+      .source_infos
+      .iter_mut()
+      .filter(|info| info.source.url == source.url && info.source.distro == source.distro)
+    {
+      info.should_update = Some(true);
+    }
+    Ok(())
+  }
+
   fn save_cache_inrelease(&self, content: &str, source: &Source) -> Result<(), DownloadError> {
     fs::write(
       self.cache_dir.join(source.inrelease_filename()).as_path(),
@@ -290,22 +419,32 @@ impl PackageDownloadClient {
   }
 }
 
-fn read_inrelease_cache(source: &Source, cache_dir: &Path) -> Option<String> {
-  let filepathbuf = cache_dir.join(source.inrelease_filename());
-  match fs::read_to_string(filepathbuf.as_path()) {
-    Ok(s) => Some(s),
-    Err(_) => None,
-  }
+// fetch `url` and verify its body against the entry listed in Release.
+fn fetch_verified(
+  transport: &dyn Transport,
+  url: &str,
+  expected: &ReleaseFile,
+) -> Result<Vec<u8>, DownloadError> {
+  let bytes = transport.fetch(url)?;
+  verify_checksum(&bytes, expected.size, &expected.sha256, url)?;
+  Ok(bytes)
 }
 
-fn search_md5(target: &str, inrelease: &str) -> Option<String> {
-  for line in inrelease.lines() {
-    if line.contains(target) {
-      let parts: Vec<&str> = line.split_whitespace().collect();
-      return Some(parts.first().unwrap().trim().to_string());
-    }
+// whether the mirror doesn't serve the file at all.
+fn is_not_found(err: &DownloadError) -> bool {
+  match err {
+    DownloadError::InvalidStatusCode { status } => *status == StatusCode::NOT_FOUND,
+    DownloadError::LocalFileNotFound { .. } => true,
+    _ => false,
   }
-  None
+}
+
+// SHA256 of the most preferred variant of `Packages` of `source`, which identifies its content.
+fn index_hash(release: &Release, source: &Source) -> Option<String> {
+  listed_variants(release, &source.packages_index_path())
+    .into_iter()
+    .next()
+    .map(|(_, file)| file.sha256)
 }
 
 fn check_listdb_exists(package_cache_dir: &Path, source: &Source) -> bool {
@@ -0,0 +1,225 @@
+/*
+ This file implements PDiff, incremental update of `Packages` by `Packages.diff/Index`.
+ Each patch is an ed-style script which converts an old `Packages` into the next one.
+*/
+
+use crate::source::release::ReleaseFile;
+
+use std::collections::HashMap;
+
+// `Packages.diff/Index`, which lists history of `Packages` and patches between them.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct DiffIndex {
+  pub current: Option<ReleaseFile>, // `SHA256-Current`: the latest `Packages`
+  pub history: Vec<(String, ReleaseFile)>, // `SHA256-History`: old `Packages` of each patch name
+  pub patches: HashMap<String, ReleaseFile>, // `SHA256-Patches`: uncompressed patches
+  pub downloads: HashMap<String, ReleaseFile>, // `SHA256-Download`: compressed patches
+  pub merged: bool, // each patch converts its `Packages` directly into the latest one
+}
+
+impl DiffIndex {
+  pub fn from(content: &str) -> Self {
+    let mut index = Self::default();
+    let mut section = String::new();
+
+    for line in content.lines() {
+      // continuation lines of multi-line fields: `<hash> <size> <name>`
+      if line.starts_with(' ') || line.starts_with('\t') {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 3 {
+          continue;
+        }
+        let file = match parts[1].parse() {
+          Ok(size) => ReleaseFile {
+            size,
+            sha256: parts[0].to_lowercase(),
+          },
+          Err(_) => continue,
+        };
+        let name = parts[2].to_string();
+        match section.as_str() {
+          "sha256-history" => index.history.push((name, file)),
+          "sha256-patches" => {
+            index.patches.insert(name, file);
+          }
+          // downloaded patches have an extension, eg) `2021-01-01-0000.00.gz`
+          "sha256-download" => {
+            let name = name.strip_suffix(".gz").unwrap_or(&name).to_string();
+            index.downloads.insert(name, file);
+          }
+          _ => {}
+        }
+        continue;
+      }
+      let (field, value) = match line.split_once(':') {
+        Some((field, value)) => (field.trim().to_lowercase(), value.trim()),
+        None => continue,
+      };
+      match field.as_str() {
+        "sha256-current" => {
+          let parts: Vec<&str> = value.split_whitespace().collect();
+          if parts.len() == 2 {
+            if let Ok(size) = parts[1].parse() {
+              index.current = Some(ReleaseFile {
+                size,
+                sha256: parts[0].to_lowercase(),
+              });
+            }
+          }
+        }
+        "x-patch-precedence" => index.merged = value == "merged",
+        _ => {}
+      }
+      section = field;
+    }
+
+    index
+  }
+
+  // names of patches to apply in order to update `Packages` whose SHA256 is `sha256`.
+  // Returns `None` if `sha256` is not found in the history, ie) PDiff can't be used.
+  pub fn patches_for(&self, sha256: &str) -> Option<Vec<String>> {
+    if self.current.as_ref()?.sha256 == sha256 {
+      return Some(vec![]);
+    }
+    let position = self
+      .history
+      .iter()
+      .position(|(_, file)| file.sha256 == sha256)?;
+    let names = self.history[position..]
+      .iter()
+      .map(|(name, _)| name.clone());
+    if self.merged {
+      Some(names.take(1).collect())
+    } else {
+      Some(names.collect())
+    }
+  }
+}
+
+// apply ed-style `script` generated by `diff --ed` to `content`.
+// Commands are applied in the given order, which is descending order of line numbers.
+pub fn apply_ed(content: &str, script: &str) -> Result<String, String> {
+  let mut lines: Vec<&str> = content.lines().collect();
+  let mut commands = script.lines();
+
+  while let Some(command) = commands.next() {
+    if command.is_empty() {
+      continue;
+    }
+    let op_ix = command
+      .find(|c: char| !c.is_ascii_digit() && c != ',')
+      .ok_or_else(|| format!("invalid command: {}", command))?;
+    let (range, op) = command.split_at(op_ix);
+    let parse = |s: &str| {
+      s.parse::<usize>()
+        .map_err(|_| format!("invalid command: {}", command))
+    };
+    let (start, end) = match range.split_once(',') {
+      Some((start, end)) => (parse(start)?, parse(end)?),
+      None => (parse(range)?, parse(range)?),
+    };
+    if start > end || end > lines.len() {
+      return Err(format!("line out of range: {}", command));
+    }
+
+    // text to be added, which ends with a line of single `.`
+    let mut read_text = || {
+      let mut text = vec![];
+      loop {
+        match commands.next() {
+          Some(".") => return Ok(text),
+          Some(line) => text.push(line),
+          None => return Err(format!("unterminated text of {}", command)),
+        }
+      }
+    };
+    match op {
+      "a" => {
+        let text = read_text()?;
+        lines.splice(start..start, text);
+      }
+      "c" if start > 0 => {
+        let text = read_text()?;
+        lines.splice(start - 1..end, text);
+      }
+      "d" if start > 0 => {
+        lines.drain(start - 1..end);
+      }
+      _ => return Err(format!("unsupported command: {}", command)),
+    }
+  }
+
+  let mut result = lines.join("\n");
+  if !lines.is_empty() {
+    result.push('\n');
+  }
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const INDEX: &str = "SHA256-Current: cccc 300
+SHA256-History:
+ aaaa 100 2021-01-01-0000.00
+ bbbb 200 2021-01-02-0000.00
+SHA256-Patches:
+ 1111 10 2021-01-01-0000.00
+ 2222 20 2021-01-02-0000.00
+SHA256-Download:
+ 3333 5 2021-01-01-0000.00.gz
+ 4444 6 2021-01-02-0000.00.gz
+";
+
+  #[test]
+  fn test_parse_diff_index() {
+    let index = DiffIndex::from(INDEX);
+    assert_eq!(
+      index.current,
+      Some(ReleaseFile {
+        size: 300,
+        sha256: "cccc".into()
+      })
+    );
+    assert_eq!(index.history.len(), 2);
+    assert_eq!(index.patches["2021-01-02-0000.00"].sha256, "2222");
+    assert_eq!(index.downloads["2021-01-01-0000.00"].size, 5);
+    assert!(!index.merged);
+
+    assert_eq!(
+      index.patches_for("aaaa").unwrap(),
+      vec!["2021-01-01-0000.00", "2021-01-02-0000.00"]
+    );
+    assert_eq!(
+      index.patches_for("bbbb").unwrap(),
+      vec!["2021-01-02-0000.00"]
+    );
+    assert!(index.patches_for("cccc").unwrap().is_empty());
+    assert!(index.patches_for("dddd").is_none());
+
+    // merged patches convert any old one into the latest one at once.
+    let index = DiffIndex::from(&format!("X-Patch-Precedence: merged\n{}", INDEX));
+    assert!(index.merged);
+    assert_eq!(
+      index.patches_for("aaaa").unwrap(),
+      vec!["2021-01-01-0000.00"]
+    );
+  }
+
+  #[test]
+  fn test_apply_ed() {
+    let content = "Package: a\nVersion: 1\n\nPackage: b\nVersion: 1\n\nPackage: c\nVersion: 1\n";
+    // update `c`, remove `b` and add `0` at the head.
+    let script = "8c\nVersion: 2\n.\n4,6d\n0a\nPackage: 0\nVersion: 1\n\n.\n";
+    assert_eq!(
+      apply_ed(content, script).unwrap(),
+      "Package: 0\nVersion: 1\n\nPackage: a\nVersion: 1\n\nPackage: c\nVersion: 2\n"
+    );
+
+    assert!(apply_ed(content, "100d\n").is_err());
+    assert!(apply_ed(content, "1c\nhoge\n").is_err());
+    assert!(apply_ed(content, "s/.//\n").is_err());
+  }
+}
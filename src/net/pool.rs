@@ -0,0 +1,211 @@
+/*
+ This file implements a bounded worker pool for parallel downloads.
+ Each worker shares a single transport given by `Context`,
+ and the number of simultaneous connections to a host is limited.
+ NOTE: the limit applies to the primary host of each task only. Once a task falls over to
+ another mirror in `mirror::with_failover`, its requests aren't counted against that mirror.
+*/
+
+use super::error::DownloadError;
+use super::mirror::RetryPolicy;
+use super::transport::Transport;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+pub const DEFAULT_JOBS: usize = 4;
+pub const MAX_CONNECTIONS_PER_HOST: usize = 2;
+
+pub struct DownloadPool {
+  jobs: usize,
+  per_host: usize,
+  transport: Arc<dyn Transport>,
+  retry_policy: RetryPolicy,
+}
+
+// tasks not taken by workers yet, and the number of active connections of each host.
+struct PoolState {
+  queue: VecDeque<usize>,
+  connections: HashMap<String, usize>,
+}
+
+impl DownloadPool {
+  pub fn new(jobs: usize, transport: Arc<dyn Transport>) -> Self {
+    Self {
+      jobs: jobs.max(1),
+      per_host: MAX_CONNECTIONS_PER_HOST,
+      transport,
+      retry_policy: RetryPolicy::default(),
+    }
+  }
+
+  pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+    self.retry_policy = retry_policy;
+    self
+  }
+
+  pub fn transport(&self) -> &dyn Transport {
+    self.transport.as_ref()
+  }
+
+  pub fn retry_policy(&self) -> &RetryPolicy {
+    &self.retry_policy
+  }
+
+  // run `f` for each task in parallel with showing progress of each worker.
+  // `describe` returns URL and label of a task, whose host is used to limit connections.
+  // The host is of the primary URL, even if `f` requests other mirrors on failover.
+  // Results are returned in the same order as `tasks` regardless of completion order,
+  // so the first error in the returned list is deterministic.
+  pub fn run<T, R, D, F>(&self, tasks: &[T], describe: D, f: F) -> Vec<Result<R, DownloadError>>
+  where
+    T: Sync,
+    R: Send,
+    D: Fn(&T) -> (String, String) + Sync,
+    F: Fn(&dyn Transport, &T) -> Result<R, DownloadError> + Sync,
+  {
+    let state = Mutex::new(PoolState {
+      queue: (0..tasks.len()).collect(),
+      connections: HashMap::new(),
+    });
+    let released = Condvar::new();
+    let results: Mutex<Vec<Option<Result<R, DownloadError>>>> =
+      Mutex::new((0..tasks.len()).map(|_| None).collect());
+
+    let multi = MultiProgress::new();
+    let total = multi.add(crate::util::default_progbar(tasks.len() as u64));
+    let worker_style = ProgressStyle::default_spinner().template("   {spinner} {msg}");
+
+    thread::scope(|scope| {
+      for _ in 0..self.jobs.min(tasks.len()) {
+        let bar = multi.add(ProgressBar::new_spinner());
+        bar.set_style(worker_style.clone());
+        let total = total.clone();
+        let (state, released, results, describe, f) = (&state, &released, &results, &describe, &f);
+        scope.spawn(move || {
+          while let Some((ix, host)) = self.take(state, released, tasks, describe) {
+            let (_, label) = describe(&tasks[ix]);
+            bar.set_message(label);
+            bar.enable_steady_tick(100);
+            let result = f(self.transport(), &tasks[ix]);
+            self.release(state, released, &host);
+
+            results.lock().unwrap()[ix] = Some(result);
+            total.inc(1);
+            if total.position() >= tasks.len() as u64 {
+              total.abandon_with_message("Complete");
+            }
+          }
+          bar.finish_and_clear();
+        });
+      }
+      if tasks.is_empty() {
+        total.abandon_with_message("Complete");
+      }
+      let _ = multi.join();
+    });
+
+    results
+      .into_inner()
+      .unwrap()
+      .into_iter()
+      .map(|result| result.unwrap())
+      .collect()
+  }
+
+  // take the first task whose host has a free connection slot.
+  // Returns `None` if all tasks are taken.
+  fn take<T, D>(
+    &self,
+    state: &Mutex<PoolState>,
+    released: &Condvar,
+    tasks: &[T],
+    describe: &D,
+  ) -> Option<(usize, String)>
+  where
+    D: Fn(&T) -> (String, String),
+  {
+    let mut state = state.lock().unwrap();
+    loop {
+      if state.queue.is_empty() {
+        return None;
+      }
+      let available = state.queue.iter().enumerate().find_map(|(pos, &ix)| {
+        let host = host_of(&describe(&tasks[ix]).0);
+        if *state.connections.get(&host).unwrap_or(&0) < self.per_host {
+          Some((pos, ix, host))
+        } else {
+          None
+        }
+      });
+      match available {
+        Some((pos, ix, host)) => {
+          state.queue.remove(pos);
+          *state.connections.entry(host.clone()).or_insert(0) += 1;
+          return Some((ix, host));
+        }
+        None => state = released.wait(state).unwrap(),
+      }
+    }
+  }
+
+  fn release(&self, state: &Mutex<PoolState>, released: &Condvar, host: &str) {
+    let mut state = state.lock().unwrap();
+    if let Some(count) = state.connections.get_mut(host) {
+      *count -= 1;
+    }
+    released.notify_all();
+  }
+}
+
+fn host_of(url: &str) -> String {
+  match reqwest::Url::parse(url) {
+    Ok(url) => url.host_str().unwrap_or("").to_string(),
+    Err(_) => String::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::net::mock::MockTransport;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  #[test]
+  fn test_pool_order_and_host_limit() {
+    let pool = DownloadPool::new(8, Arc::new(MockTransport::new()));
+    let tasks: Vec<usize> = (0..20).collect();
+    let active = AtomicUsize::new(0);
+    let max_active = AtomicUsize::new(0);
+
+    // every task goes to the same host, so at most `per_host` tasks run at once.
+    let results = pool.run(
+      &tasks,
+      |task| ("http://example.com/".into(), task.to_string()),
+      |_, task| {
+        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+        max_active.fetch_max(now, Ordering::SeqCst);
+        thread::sleep(std::time::Duration::from_millis(5));
+        active.fetch_sub(1, Ordering::SeqCst);
+        if task % 7 == 3 {
+          Err(DownloadError::FileNotFound {
+            name: task.to_string(),
+          })
+        } else {
+          Ok(task * 2)
+        }
+      },
+    );
+
+    assert!(max_active.load(Ordering::SeqCst) <= MAX_CONNECTIONS_PER_HOST);
+    assert_eq!(results.len(), tasks.len());
+    assert_eq!(*results[1].as_ref().unwrap(), 2);
+    // the first error is always of the first failed task.
+    match results.into_iter().collect::<Result<Vec<_>, _>>() {
+      Err(DownloadError::FileNotFound { name }) => assert_eq!(name, "3"),
+      _ => panic!("expected error"),
+    }
+  }
+}
@@ -0,0 +1,183 @@
+/*
+ This file implements verification of OpenPGP signatures of clearsigned `InRelease`.
+*/
+
+use super::error::DownloadError;
+
+use pgp::{cleartext::CleartextSignedMessage, Deserializable, SignedPublicKey};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// set of public keys which are trusted to sign `InRelease`.
+#[derive(Debug, Default, Clone)]
+pub struct Keyring {
+  keys: Vec<SignedPublicKey>,
+}
+
+impl Keyring {
+  // read a keyring file. Both armored and binary keyrings are accepted.
+  pub fn read(path: &Path) -> Result<Self, DownloadError> {
+    let bytes = fs::read(path)?;
+    Self::from_bytes(&bytes).map_err(|msg| DownloadError::InvalidKeyring {
+      path: path.to_string_lossy().to_string(),
+      msg,
+    })
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+    let (keys, _) = SignedPublicKey::from_reader_many(bytes).map_err(|err| err.to_string())?;
+    let keys = keys
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|err| err.to_string())?;
+    Ok(Self { keys })
+  }
+
+  // read trusted keyrings of apt: `trusted.gpg` and `*.gpg`/`*.asc` in `trusted.gpg.d`.
+  // Unreadable keyrings are just ignored like apt does.
+  pub fn read_trusted(source_dir: &Path) -> Self {
+    let mut paths: Vec<PathBuf> = vec![source_dir.join("trusted.gpg")];
+    if let Ok(entries) = fs::read_dir(source_dir.join("trusted.gpg.d")) {
+      let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+          path.is_file()
+            && matches!(
+              path.extension().and_then(|ext| ext.to_str()),
+              Some("gpg") | Some("asc")
+            )
+        })
+        .collect();
+      files.sort();
+      paths.extend(files);
+    }
+
+    let mut keyring = Self::default();
+    for path in paths.iter().filter(|path| path.is_file()) {
+      if let Ok(ring) = Self::read(path) {
+        keyring.keys.extend(ring.keys);
+      }
+    }
+    keyring
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keys.is_empty()
+  }
+
+  // check if `message` is signed by any key (or its subkeys) in this keyring.
+  fn verify(&self, message: &CleartextSignedMessage) -> bool {
+    self.keys.iter().any(|key| {
+      message.verify(key).is_ok()
+        || key
+          .public_subkeys
+          .iter()
+          .any(|subkey| message.verify(subkey).is_ok())
+    })
+  }
+}
+
+// verify that clearsigned `InRelease` of `target` is signed by a key in `keyring`,
+// and return the signed text, which is the only part to be trusted.
+pub fn verify_inrelease(
+  content: &str,
+  keyring: &Keyring,
+  target: &str,
+) -> Result<String, DownloadError> {
+  let invalid = |reason: &str| DownloadError::InvalidSignature {
+    target: target.to_string(),
+    reason: reason.to_string(),
+  };
+
+  if !content
+    .trim_start()
+    .starts_with("-----BEGIN PGP SIGNED MESSAGE-----")
+  {
+    return Err(invalid("InRelease is not signed"));
+  }
+  let (message, _) =
+    CleartextSignedMessage::from_string(content).map_err(|err| invalid(&err.to_string()))?;
+  if message.signatures().is_empty() {
+    return Err(invalid("InRelease is not signed"));
+  }
+  if keyring.is_empty() {
+    return Err(invalid("no trusted keyring is available"));
+  }
+  if !keyring.verify(&message) {
+    return Err(invalid("signature is not made by trusted keys"));
+  }
+
+  // signed text is normalized into CRLF line endings, which `Release` never uses.
+  Ok(message.signed_text().replace("\r\n", "\n"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use pgp::{
+    crypto::{hash::HashAlgorithm, sym::SymmetricKeyAlgorithm},
+    types::{CompressionAlgorithm, SecretKeyTrait},
+    ArmorOptions, KeyType, SecretKeyParamsBuilder, SignedSecretKey,
+  };
+  use smallvec::smallvec;
+
+  fn generate_key(uid: &str) -> (SignedSecretKey, Keyring) {
+    let mut rng = rand::thread_rng();
+    let params = SecretKeyParamsBuilder::default()
+      .key_type(KeyType::EdDSALegacy)
+      .can_certify(true)
+      .can_sign(true)
+      .primary_user_id(uid.into())
+      .preferred_symmetric_algorithms(smallvec![SymmetricKeyAlgorithm::AES256])
+      .preferred_hash_algorithms(smallvec![HashAlgorithm::SHA2_256])
+      .preferred_compression_algorithms(smallvec![CompressionAlgorithm::ZLIB])
+      .build()
+      .unwrap();
+    let secret_key = params
+      .generate(&mut rng)
+      .unwrap()
+      .sign(&mut rng, String::new)
+      .unwrap();
+    let public_key = secret_key
+      .public_key()
+      .sign(&mut rng, &secret_key, String::new)
+      .unwrap();
+    let armored = public_key
+      .to_armored_bytes(ArmorOptions::default())
+      .unwrap();
+    (secret_key, Keyring::from_bytes(&armored).unwrap())
+  }
+
+  fn sign(text: &str, key: &SignedSecretKey) -> String {
+    CleartextSignedMessage::sign(rand::thread_rng(), text, key, String::new)
+      .unwrap()
+      .to_armored_string(ArmorOptions::default())
+      .unwrap()
+  }
+
+  #[test]
+  fn test_verify_inrelease() {
+    let (secret_key, keyring) = generate_key("rapt2 test <test@example.com>");
+    let (_, other_keyring) = generate_key("other <other@example.com>");
+    let text = "Origin: Ubuntu\nSuite: focal\nMD5Sum:\n 7a1e4d7c7d1f1d42ef0c0d8e8a1b4cd1 1 main/binary-amd64/Packages\n";
+    let signed = sign(text, &secret_key);
+
+    // correctly signed, and only the signed text is returned.
+    assert_eq!(verify_inrelease(&signed, &keyring, "test").unwrap(), text);
+
+    // signed by untrusted key
+    assert!(matches!(
+      verify_inrelease(&signed, &other_keyring, "test"),
+      Err(DownloadError::InvalidSignature { .. })
+    ));
+    assert!(verify_inrelease(&signed, &Keyring::default(), "test").is_err());
+
+    // tampered content
+    let tampered = signed.replace("Suite: focal", "Suite: evil");
+    assert!(verify_inrelease(&tampered, &keyring, "test").is_err());
+
+    // missing signature
+    assert!(verify_inrelease(text, &keyring, "test").is_err());
+  }
+}
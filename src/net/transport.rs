@@ -0,0 +1,226 @@
+/*
+ This file defines `Transport`, which fetches index files and archives by URL.
+ HTTP and local repositories are served by each implementation,
+ and tests inject `MockTransport` through `Context` instead of real mirrors.
+*/
+
+use super::config::NetConfig;
+use super::error::DownloadError;
+use super::local::{self, FileTransport};
+use crate::util::{ims2timestamp, timestamp2ims};
+
+use once_cell::sync::OnceCell;
+use reqwest::header::{CONTENT_LENGTH, IF_MODIFIED_SINCE, LAST_MODIFIED, RANGE};
+use reqwest::{Proxy, StatusCode};
+use std::fmt::Debug;
+use std::time::SystemTime;
+
+// size and last modified time of a file, which are `None` if unknown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+  pub size: Option<u64>,
+  pub modified: Option<SystemTime>,
+}
+
+// body of ranged fetch.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeBody {
+  Partial(Vec<u8>), // the rest of the file from requested position
+  Whole(Vec<u8>),   // whole file, cuz range is not requested or not supported
+}
+
+impl RangeBody {
+  pub fn into_bytes(self) -> Vec<u8> {
+    match self {
+      Self::Partial(bytes) | Self::Whole(bytes) => bytes,
+    }
+  }
+}
+
+pub trait Transport: Debug + Send + Sync {
+  // fetch `url` only if it's modified after `since`, otherwise `Ok(None)` is returned.
+  // If `since` is `None`, it's always fetched.
+  fn fetch_if_modified(
+    &self,
+    url: &str,
+    since: Option<SystemTime>,
+  ) -> Result<Option<Vec<u8>>, DownloadError>;
+
+  // fetch `url` from `start` byte.
+  fn fetch_range(&self, url: &str, start: u64) -> Result<RangeBody, DownloadError>;
+
+  fn metadata(&self, url: &str) -> Result<Metadata, DownloadError>;
+
+  fn fetch(&self, url: &str) -> Result<Vec<u8>, DownloadError> {
+    Ok(self.fetch_range(url, 0)?.into_bytes())
+  }
+}
+
+// transport used by default, which chooses HTTP or local one by scheme of URL.
+#[derive(Debug, Default)]
+pub struct DefaultTransport {
+  http: HttpTransport,
+  file: FileTransport,
+}
+
+impl DefaultTransport {
+  pub fn new(config: NetConfig) -> Self {
+    Self {
+      http: HttpTransport::new(config),
+      file: FileTransport,
+    }
+  }
+
+  fn route(&self, url: &str) -> &dyn Transport {
+    if local::is_local(url) {
+      &self.file
+    } else {
+      &self.http
+    }
+  }
+}
+
+impl Transport for DefaultTransport {
+  fn fetch_if_modified(
+    &self,
+    url: &str,
+    since: Option<SystemTime>,
+  ) -> Result<Option<Vec<u8>>, DownloadError> {
+    self.route(url).fetch_if_modified(url, since)
+  }
+
+  fn fetch_range(&self, url: &str, start: u64) -> Result<RangeBody, DownloadError> {
+    self.route(url).fetch_range(url, start)
+  }
+
+  fn metadata(&self, url: &str) -> Result<Metadata, DownloadError> {
+    self.route(url).metadata(url)
+  }
+}
+
+// HTTP(S) transport sharing a single connection-reusing client configured by `NetConfig`.
+// The client is built on the first request, so commands without downloads never pay for it.
+#[derive(Debug, Default)]
+pub struct HttpTransport {
+  config: NetConfig,
+  client: OnceCell<reqwest::blocking::Client>,
+}
+
+impl HttpTransport {
+  pub fn new(config: NetConfig) -> Self {
+    Self {
+      config,
+      client: OnceCell::new(),
+    }
+  }
+
+  fn client(&self) -> Result<&reqwest::blocking::Client, DownloadError> {
+    self
+      .client
+      .get_or_try_init(|| {
+        let config = self.config.clone();
+        let mut builder = reqwest::blocking::Client::builder()
+          // compressed indices are verified as they are, so never decode them transparently.
+          .gzip(false)
+          .user_agent(config.user_agent.as_str())
+          .timeout(config.timeout);
+        if let Some(timeout) = config.connect_timeout {
+          builder = builder.connect_timeout(timeout);
+        }
+        // proxies of environment variables are already merged into `config`,
+        // and adding a proxy disables reqwest's own detection of them.
+        builder
+          .proxy(Proxy::custom(move |url| config.proxy_for(url)))
+          .build()
+      })
+      .map_err(DownloadError::RequestFailed)
+  }
+}
+
+impl Transport for HttpTransport {
+  fn fetch_if_modified(
+    &self,
+    url: &str,
+    since: Option<SystemTime>,
+  ) -> Result<Option<Vec<u8>>, DownloadError> {
+    let mut builder = self.client()?.get(url);
+    if let Some(since) = since {
+      builder = builder.header(IF_MODIFIED_SINCE, timestamp2ims(since));
+    }
+    let res = builder.send()?;
+    match res.status() {
+      StatusCode::NOT_MODIFIED if since.is_some() => Ok(None),
+      StatusCode::OK => Ok(Some(res.bytes()?.to_vec())),
+      status => Err(DownloadError::InvalidStatusCode { status }),
+    }
+  }
+
+  fn fetch_range(&self, url: &str, start: u64) -> Result<RangeBody, DownloadError> {
+    let mut builder = self.client()?.get(url);
+    if start != 0 {
+      builder = builder.header(RANGE, format!("bytes={}-", start));
+    }
+    let res = builder.send()?;
+    match res.status() {
+      // server supports `Range`, so only the rest is returned.
+      StatusCode::PARTIAL_CONTENT if start != 0 => Ok(RangeBody::Partial(res.bytes()?.to_vec())),
+      StatusCode::OK => Ok(RangeBody::Whole(res.bytes()?.to_vec())),
+      status => Err(DownloadError::InvalidStatusCode { status }),
+    }
+  }
+
+  fn metadata(&self, url: &str) -> Result<Metadata, DownloadError> {
+    let res = self.client()?.head(url).send()?;
+    if res.status() != StatusCode::OK {
+      return Err(DownloadError::InvalidStatusCode {
+        status: res.status(),
+      });
+    }
+    let header = |name| {
+      res
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+    };
+    Ok(Metadata {
+      size: header(CONTENT_LENGTH).and_then(|len| len.parse().ok()),
+      modified: header(LAST_MODIFIED).and_then(|date| ims2timestamp(&date)),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  #[test]
+  fn test_default_transport_routes_local() {
+    let path = fs::canonicalize("./tests/resources/index/Packages").unwrap();
+    let url = format!("file:{}", path.to_string_lossy());
+    let plain = fs::read(&path).unwrap();
+    let transport = DefaultTransport::default();
+
+    assert_eq!(transport.fetch(&url).unwrap(), plain);
+    assert_eq!(
+      transport.fetch_range(&url, 10).unwrap(),
+      RangeBody::Partial(plain[10..].to_vec())
+    );
+    let meta = transport.metadata(&url).unwrap();
+    assert_eq!(meta.size, Some(plain.len() as u64));
+    assert_eq!(
+      transport.fetch_if_modified(&url, meta.modified).unwrap(),
+      None
+    );
+    // HTTP client is never built for local URLs.
+    assert!(transport.http.client.get().is_none());
+  }
+
+  #[test]
+  fn test_ims_roundtrip() {
+    let t = ims2timestamp("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    assert_eq!(timestamp2ims(t), "Sun, 06 Nov 1994 08:49:37 GMT");
+    assert!(ims2timestamp("hoge").is_none());
+  }
+}
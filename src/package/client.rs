@@ -9,20 +9,25 @@
     (It would not happen that you read list DB after updating it.)
 */
 
-use super::package::EntryType;
+use super::package::{Depends, DependsAnyOf, EntryType, MultiArch, PackageSpec};
+use super::version::VersionComp;
 use super::{error::PackageError, package::Package, parser};
-use crate::dpkg::client::{DpkgClient, StatusComp};
+use crate::apt::preferences::CandidatePolicy;
+use crate::dpkg::client::{compare_with_installed, DpkgClient, StatusComp};
+use crate::dpkg::status::DpkgStatusStatus;
 use crate::source::source::{ArchivedType, Source};
 
 use glob;
+use regex::RegexBuilder;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 pub struct PackageClient {
-  cache_dir: PathBuf,                            // package cache dir
-  list_cache: HashMap<String, HashSet<Package>>, // list DB cache
+  cache_dir: PathBuf,                        // package cache dir
+  list_cache: HashMap<String, Vec<Package>>, // list DB cache, keeping all versions
+  policy: CandidatePolicy,                   // policy to select candidate versions
 }
 
 impl PackageClient {
@@ -36,30 +41,59 @@ impl PackageClient {
       Ok(Self {
         cache_dir,
         list_cache: HashMap::new(),
+        policy: CandidatePolicy::default(),
       })
     }
   }
 
+  // set apt preferences used to select candidate versions.
+  // Without it, the highest version is always the candidate.
+  pub fn set_policy(&mut self, policy: CandidatePolicy) {
+    self.policy = policy;
+  }
+
+  pub fn policy(&self) -> &CandidatePolicy {
+    &self.policy
+  }
+
   // read a single list file.
   // `filename` is relative filename to `self.cache_dir`.
+  // If the file lists several versions of a package, the first one is returned.
   pub fn read_single_file(&mut self, filename: &str) -> Result<HashSet<Package>, PackageError> {
-    if self.list_cache.contains_key(filename) {
-      let packages = self.list_cache.get(filename).unwrap();
-      Ok(packages.clone())
-    } else {
-      let content = self.read_single_file_raw(filename)?;
-      let packages = parser::parse_entries_as_binary(&content)?;
-      self
-        .list_cache
-        .insert(filename.to_string(), packages.clone());
-      Ok(packages)
+    Ok(self.read_single_file_all(filename)?.into_iter().collect())
+  }
+
+  // read a single list file with all versions of each package.
+  fn read_single_file_all(&mut self, filename: &str) -> Result<Vec<Package>, PackageError> {
+    if let Some(packages) = self.list_cache.get(filename) {
+      return Ok(packages.clone());
     }
+    let content = self.read_single_file_raw(filename)?;
+    let packages = parser::parse_all_entries_as_binary(&content)?;
+    self
+      .list_cache
+      .insert(filename.to_string(), packages.clone());
+    Ok(packages)
   }
 
   pub fn read_single_source(&mut self, source: &Source) -> Result<HashSet<Package>, PackageError> {
     self.read_single_file(&source.cache_filename())
   }
 
+  // read cached binary index of `source`, which is `None` if it's not fetched yet or `deb-src`.
+  // All versions listed in the index are kept.
+  // Unlike missing files, broken ones are reported as errors.
+  fn read_fetched_source(&mut self, source: &Source) -> Result<Option<Vec<Package>>, PackageError> {
+    if source.archive_type != ArchivedType::DEB {
+      return Ok(None);
+    }
+    match self.read_single_file_all(&source.cache_filename()) {
+      Ok(packages) => Ok(Some(packages)),
+      Err(PackageError::FileNotFound { .. }) => Ok(None),
+      Err(err) => Err(err),
+    }
+  }
+
   // NOTE: this func doesn't use cache
   pub fn read_single_file_raw(&self, filename: &str) -> Result<String, PackageError> {
     let pathbuf = self.cache_dir.join(filename);
@@ -140,36 +174,64 @@ impl PackageClient {
     Ok(results)
   }
 
+  // read candidate versions of all packages in `sources` based on the policy.
+  // If the policy keeps the installed version in `installeds`, other versions are never returned.
   pub fn read_all_from_source_with_source(
     &mut self,
     sources: &[Source],
+    installeds: &HashSet<Package>,
   ) -> Result<HashSet<PackageWithSource>, PackageError> {
-    let mut results: HashSet<PackageWithSource> = HashSet::new();
+    let index = self.read_all_versions_index(sources, installeds)?;
+
+    Ok(
+      index
+        .versions
+        .into_values()
+        .filter_map(|versions| {
+          let best = versions.into_iter().next()?;
+          match installed_of(installeds, &best.package) {
+            Some(installed)
+              if best.package.version != installed.version
+                && !self.policy.wins_over_installed(&best, installed) =>
+            {
+              None
+            }
+            _ => Some(best),
+          }
+        })
+        .collect(),
+    )
+  }
+
+  // read all versions of a package named `name` across all `sources`.
+  // Unlike `read_all_from_source_with_source()`, lower versions are kept.
+  // Result is sorted in descending order of version.
+  pub fn read_all_versions_with_source(
+    &mut self,
+    name: &str,
+    sources: &[Source],
+  ) -> Result<Vec<PackageWithSource>, PackageError> {
+    let mut results = vec![];
+    // `name` may be qualified by architecture, eg) `libc6:i386`.
+    let (name, arch) = name.split_once(':').unwrap_or((name, ""));
+    let target = Package {
+      name: name.into(),
+      arch: arch.into(),
+      ..Default::default()
+    };
 
     for source in sources {
-      // ignore error cuz lists file contains unreadable files such as `lock`.
-      if let Ok(packages) = self.read_single_file(&source.cache_filename()) {
-        let packages_with_source: Vec<PackageWithSource> = packages
-          .into_iter()
-          .map(|package| PackageWithSource {
+      if let Some(packages) = self.read_fetched_source(source)? {
+        for package in packages.into_iter().filter(|package| package == &target) {
+          results.push(PackageWithSource {
             package,
             source: source.clone(),
             dpkg_status: None,
-          })
-          .collect();
-        for package_with_source in packages_with_source {
-          if results.contains(&package_with_source) {
-            let existing = results.get(&package_with_source).unwrap().clone();
-            if existing.package.version < package_with_source.package.version {
-              results.remove(&existing); // must remove first
-              results.insert(package_with_source);
-            }
-          } else {
-            results.insert(package_with_source);
-          }
+          });
         }
       }
     }
+    results.sort_by(|a, b| b.package.version.partial_cmp(&a.package.version).unwrap());
 
     Ok(results)
   }
@@ -190,7 +252,7 @@ impl PackageClient {
       .filter(|source| source.archive_type == ArchivedType::DEB)
       .collect();
 
-    let packages = self.read_all_from_source_with_source(&sources)?;
+    let packages = self.read_all_from_source_with_source(&sources, &HashSet::new())?;
     Ok(
       packages
         .into_iter()
@@ -199,119 +261,392 @@ impl PackageClient {
     )
   }
 
+  // search packages from list DB by regex.
+  // The regex is matched against name, short and long description of packages.
+  // If `names_only` is true, the regex is matched only against package names.
+  pub fn search_by_regex_with_source(
+    &mut self,
+    pattern: &str,
+    names_only: bool,
+    sources: Vec<Source>,
+  ) -> Result<HashSet<PackageWithSource>, PackageError> {
+    let re = match RegexBuilder::new(pattern).case_insensitive(true).build() {
+      Ok(re) => re,
+      Err(_) => {
+        return Err(PackageError::InvalidPattern {
+          pattern: pattern.into(),
+        })
+      }
+    };
+    let sources: Vec<Source> = sources
+      .into_iter()
+      .filter(|source| source.archive_type == ArchivedType::DEB)
+      .collect();
+
+    let packages = self.read_all_from_source_with_source(&sources, &HashSet::new())?;
+    Ok(
+      packages
+        .into_iter()
+        .filter(|pws| {
+          let package = &pws.package;
+          if re.is_match(&package.name) {
+            return true;
+          }
+          if names_only {
+            return false;
+          }
+          if re.is_match(&package.short_description) {
+            return true;
+          }
+          match &package.long_description {
+            Some(desc) => re.is_match(desc),
+            None => false,
+          }
+        })
+        .collect(),
+    )
+  }
+
+  // read all packages in `sources` and index them by its name and virtual package names.
+  // Packages of foreign architectures are indexed by qualified names such as `libc6:i386`.
+  // Versions of each package are sorted in the order of the policy,
+  // where the installed version in `installeds` comes first if the policy keeps it.
+  pub fn read_all_versions_index(
+    &mut self,
+    sources: &[Source],
+    installeds: &HashSet<Package>,
+  ) -> Result<PackageIndex, PackageError> {
+    let mut index: HashMap<String, Vec<PackageWithSource>> = HashMap::new();
+
+    for source in sources {
+      if let Some(packages) = self.read_fetched_source(source)? {
+        for package in packages {
+          index
+            .entry(package.full_name())
+            .or_default()
+            .push(PackageWithSource {
+              package,
+              source: source.clone(),
+              dpkg_status: None,
+            });
+        }
+      }
+    }
+    // candidate of each package comes first.
+    for versions in index.values_mut() {
+      let installed = installed_of(installeds, &versions[0].package);
+      self.policy.order(versions, installed);
+    }
+    index.retain(|_, versions| !versions.is_empty());
+
+    let providers = provider_index(&index);
+    Ok(PackageIndex {
+      versions: index,
+      providers,
+    })
+  }
+
   // Get target packages and all of its dependencies with Source information.
+  // For each package, the highest version satisfying all version constraints is chosen.
+  // `Recommends` are resolved in the best-effort way: unresolvable ones are just skipped.
   // Result is returned in flattened HashSet.
   pub fn get_package_with_deps(
     &mut self,
     name: &str,                                      // target package
     #[allow(clippy::ptr_arg)] sources: &Vec<Source>, // sources to search for packages
     ignore_installed: bool,                          // ignore already installed packages
-    mut dpkg_client: Option<&mut DpkgClient>,        // needed if `ignore_installed` is true
+    install_recommends: bool,                        // resolve `Recommends` as well
+    dpkg_client: Option<&mut DpkgClient>,            // needed if `ignore_installed` is false
   ) -> Result<HashSet<PackageWithSource>, PackageError> {
-    let pattern = glob::Pattern::new(name).unwrap();
-    let packages_with_source = self.read_all_from_source_with_source(sources)?;
+    self.get_packages_with_deps(
+      &[name.to_string()],
+      sources,
+      ignore_installed,
+      install_recommends,
+      dpkg_client,
+    )
+  }
 
-    // first, find target package itself
-    let mut target_package_ws = match packages_with_source
-      .iter()
-      .find(|package_ws| pattern.matches(&package_ws.package.name))
-    {
-      Some(target) => target.clone(),
-      None => {
-        return Err(PackageError::PackageNotFound {
-          package_name: name.into(),
-        })
-      }
+  // Get multiple target packages and the union of their dependencies in one transaction.
+  // Each target can be qualified as `<name>:<arch>`, and pinned as `<name>=<version>` or `<name>/<release>`.
+  // Already up-to-date targets are omitted from the result.
+  // Pinned targets older than installed ones are returned with `StatusComp::NEWER` (downgrade).
+  pub fn get_packages_with_deps(
+    &mut self,
+    names: &[String],                                // target packages
+    #[allow(clippy::ptr_arg)] sources: &Vec<Source>, // sources to search for packages
+    ignore_installed: bool,                          // ignore already installed packages
+    install_recommends: bool,                        // resolve `Recommends` as well
+    dpkg_client: Option<&mut DpkgClient>,            // needed if `ignore_installed` is false
+  ) -> Result<HashSet<PackageWithSource>, PackageError> {
+    let options = ResolveOptions {
+      ignore_installed,
+      install_recommends,
     };
-
-    // check target itself is already installed
-    let mut deps: HashSet<PackageWithSource> = HashSet::new();
-    if !ignore_installed {
-      match dpkg_client
-        .as_mut()
-        .unwrap()
-        .check_installed_status(&target_package_ws.package)?
+    // installed packages are read only once, and shared during resolution.
+    // Without dpkg client, nothing is regarded as installed.
+    let installeds = match dpkg_client {
+      Some(dpkg_client) if !ignore_installed => dpkg_client.get_installed_packages()?,
+      _ => HashSet::new(),
+    };
+    let index = self.read_all_versions_index(sources, &installeds)?;
+
+    // first, find target packages themselves
+    let mut target_package_wss = vec![];
+    for name in names {
+      let spec = match PackageSpec::from(name) {
+        Ok(spec) => spec,
+        Err(()) => return Err(PackageError::InvalidPackageName { name: name.into() }),
+      };
+      let pattern = glob::Pattern::new(&spec.full_name()).unwrap();
+      let mut target_package_ws = match index
+        .versions
+        .iter()
+        .find(|(name, _)| pattern.matches(name))
+        .and_then(|(_, versions)| select_pinned(versions, &spec))
       {
-        StatusComp::UPTODATE => {
-          return Ok(HashSet::new());
+        Some(target) => target.clone(),
+        None => {
+          return Err(PackageError::PackageNotFound {
+            package_name: name.into(),
+          })
         }
-        status => target_package_ws.dpkg_status = Some(status),
       };
-    }
 
-    // next, find all its dependencies recursively
-    deps.insert(target_package_ws.clone());
-    self.get_dependency_recursive(
-      &target_package_ws,
-      &packages_with_source,
-      &mut deps,
-      ignore_installed,
-      &mut dpkg_client,
-    )?;
+      // check target itself is already installed
+      if !ignore_installed {
+        let is_kept = matches!(
+          installed_of(&installeds, &target_package_ws.package),
+          Some(installed) if !self.policy.wins_over_installed(&target_package_ws, installed)
+        );
+        match compare_with_installed(&installeds, &target_package_ws.package) {
+          StatusComp::UPTODATE => continue,
+          // unless pinned by users, the installed version is kept if the policy prefers it.
+          StatusComp::OLD(_) | StatusComp::NEWER(_) if !spec.is_pinned() && is_kept => continue,
+          status => target_package_ws.dpkg_status = Some(status),
+        };
+      }
+      target_package_wss.push(target_package_ws);
+    }
 
-    Ok(deps)
+    // next, find all their dependencies recursively.
+    // If newly found constraints conflict with already chosen packages, restart resolution.
+    let mut constraints: HashMap<String, Vec<VersionConstraint>> = HashMap::new();
+    'resolution: loop {
+      let mut deps: HashSet<PackageWithSource> = target_package_wss.iter().cloned().collect();
+      for target_package_ws in &target_package_wss {
+        if !self.get_dependency_recursive(
+          target_package_ws,
+          &index,
+          &mut deps,
+          &mut constraints,
+          options,
+          &installeds,
+        )? {
+          continue 'resolution;
+        }
+      }
+      return Ok(deps);
+    }
   }
 
+  // Returns `Ok(false)` if resolution must be restarted
+  // cuz a new constraint conflicts with already chosen package.
   fn get_dependency_recursive(
     &self,
     target: &PackageWithSource,
-    all_packages_ws: &HashSet<PackageWithSource>,
+    index: &PackageIndex,
     acc: &mut HashSet<PackageWithSource>,
-    ignore_installed: bool, // ignore already installed packages
-    dpkg_client: &mut Option<&mut DpkgClient>,
-  ) -> Result<(), PackageError> {
+    constraints: &mut HashMap<String, Vec<VersionConstraint>>,
+    options: ResolveOptions,
+    installeds: &HashSet<Package>,
+  ) -> Result<bool, PackageError> {
     for dep in &target.package.depends {
-      // XXX choose arbitrary dependencie
-      let dep = &dep.depends[0];
-      // XXX it now choose first found depended-on package with same name.
-      // maybe, should choose latest version.
-      if acc.iter().any(|pws| pws.package.name == dep.package) {
+      let dep = &dep.bound_to(&target.package);
+      // skip if any of alternatives is already chosen.
+      if acc.iter().any(|pws| dep.is_satisfied_by(&pws.package)) {
         continue;
       }
-
-      let depended_on = match all_packages_ws
-        .iter()
-        .find(|pws| pws.package.name == dep.package)
-      {
-        Some(target) => {
-          if !ignore_installed {
-            match dpkg_client
-              .as_mut()
-              .unwrap()
-              .check_installed_status(&target.package)?
-            {
-              StatusComp::UPTODATE => continue,
-              status => PackageWithSource {
-                package: target.package.clone(),
-                source: target.source.clone(),
-                dpkg_status: Some(status),
-              },
-            }
-          } else {
-            target.clone()
-          }
-        }
-        None => {
-          return Err(PackageError::PackageNotFound {
-            package_name: dep.package.to_string(),
-          })
+      // if chosen one doesn't satisfy the constraint, add it and restart.
+      if let Some(chosen) = acc.iter().find(|pws| dep.targets(&pws.package)) {
+        let alternative = dep
+          .depends
+          .iter()
+          .find(|alternative| alternative.targets(&chosen.package))
+          .unwrap();
+        let key = resolve_key(alternative, &index.versions);
+        if !add_constraint(constraints, &target.package.full_name(), &key, alternative) {
+          // the constraint is already taken into account, but still unsatisfied.
+          return Err(PackageError::UnresolvedDependency {
+            depended_on: chosen.package.name.clone(),
+            depended_on_version: Box::new(chosen.package.version.clone()),
+            depending_on: target.package.full_name(),
+            depending_on_version: Box::new(alternative.version.clone().unwrap_or_default()),
+          });
         }
+        return Ok(false);
+      }
+
+      let depended_on = match self.choose_alternative(
+        &target.package.full_name(),
+        dep,
+        index,
+        constraints,
+        options.ignore_installed,
+        installeds,
+      )? {
+        Some(depended_on) => depended_on,
+        None => continue, // already satisfied by installed package
       };
 
       // append depended-on package
       acc.insert(depended_on.clone());
 
       // more search recursively
-      self.get_dependency_recursive(
+      if !self.get_dependency_recursive(
         &depended_on,
-        all_packages_ws,
+        index,
         acc,
-        ignore_installed,
-        dpkg_client,
-      )?;
+        constraints,
+        options,
+        installeds,
+      )? {
+        return Ok(false);
+      }
+    }
+
+    if options.install_recommends {
+      return self.get_recommends_recursive(target, index, acc, constraints, options, installeds);
+    }
+
+    Ok(true)
+  }
+
+  // Resolve `Recommends` of `target`.
+  // Unlike `Depends`, a recommended package which can't be resolved is just skipped,
+  // and it never forces already chosen packages to be re-chosen.
+  fn get_recommends_recursive(
+    &self,
+    target: &PackageWithSource,
+    index: &PackageIndex,
+    acc: &mut HashSet<PackageWithSource>,
+    constraints: &mut HashMap<String, Vec<VersionConstraint>>,
+    options: ResolveOptions,
+    installeds: &HashSet<Package>,
+  ) -> Result<bool, PackageError> {
+    for rec in &target.package.recommends {
+      let rec = &rec.bound_to(&target.package);
+      // skip if any of alternatives is already chosen.
+      if acc.iter().any(|pws| rec.targets(&pws.package)) {
+        continue;
+      }
+
+      let mut rec_acc = acc.clone();
+      let mut rec_constraints = constraints.clone();
+      let recommended = match self.choose_alternative(
+        &target.package.full_name(),
+        rec,
+        index,
+        &mut rec_constraints,
+        options.ignore_installed,
+        installeds,
+      ) {
+        Ok(Some(recommended)) => recommended,
+        Ok(None) | Err(_) => continue, // installed or unresolvable
+      };
+      rec_acc.insert(recommended.clone());
+      match self.get_dependency_recursive(
+        &recommended,
+        index,
+        &mut rec_acc,
+        &mut rec_constraints,
+        options,
+        installeds,
+      ) {
+        Ok(true) => {
+          *acc = rec_acc;
+          *constraints = rec_constraints;
+        }
+        // XXX restart is not propagated to avoid recommends affecting `Depends`.
+        Ok(false) | Err(_) => continue,
+      }
+    }
+
+    Ok(true)
+  }
+
+  // Choose one of alternatives of `dep` in below priority:
+  //    1. already installed one (only if `ignore_installed` is false)
+  //    2. one which has a version satisfying all collected constraints
+  // Returns `Ok(None)` if the dependency is already satisfied by installed packages,
+  // and the chosen one with `StatusComp::NEWER` if installed one is too new to satisfy it.
+  fn choose_alternative(
+    &self,
+    depending: &str,
+    dep: &DependsAnyOf,
+    index: &PackageIndex,
+    constraints: &mut HashMap<String, Vec<VersionConstraint>>,
+    ignore_installed: bool,
+    installeds: &HashSet<Package>,
+  ) -> Result<Option<PackageWithSource>, PackageError> {
+    if !ignore_installed {
+      let is_installed = installeds.iter().any(|installed| {
+        matches!(&installed.status, Some(status) if status.status == DpkgStatusStatus::Installed)
+          && dep.is_satisfied_by(installed)
+      });
+      if is_installed {
+        return Ok(None);
+      }
     }
 
-    Ok(())
+    let mut first_error = None;
+    let mut chosen = None;
+    for alternative in &dep.depends {
+      let mut alt_constraints = constraints.clone();
+      let key = resolve_key(alternative, &index.versions);
+      add_constraint(&mut alt_constraints, depending, &key, alternative);
+      match select_version(&key, &index.versions, &alt_constraints) {
+        Ok(Some(pws)) => {
+          *constraints = alt_constraints;
+          chosen = Some(pws);
+          break;
+        }
+        Ok(None) => {
+          // no real package found. search providers of virtual package.
+          if let Some(pws) = select_provider(alternative, &key, index, &alt_constraints) {
+            *constraints = alt_constraints;
+            chosen = Some(pws);
+            break;
+          }
+        }
+        Err(err) => {
+          if first_error.is_none() {
+            first_error = Some(err);
+          }
+        }
+      }
+    }
+    let chosen = match chosen {
+      Some(chosen) => chosen,
+      None => {
+        return Err(first_error.unwrap_or(PackageError::PackageNotFound {
+          package_name: dep.to_string(),
+        }))
+      }
+    };
+
+    if ignore_installed {
+      return Ok(Some(chosen.clone()));
+    }
+    match compare_with_installed(installeds, &chosen.package) {
+      StatusComp::UPTODATE => Ok(None),
+      // installed one doesn't satisfy `dep`, so the chosen one is a downgrade.
+      status => Ok(Some(PackageWithSource {
+        package: chosen.package.clone(),
+        source: chosen.source.clone(),
+        dpkg_status: Some(status),
+      })),
+    }
   }
 
   // Remove all files named "*.deb" in `archive_dir`.
@@ -324,16 +659,22 @@ impl PackageClient {
     }
 
     let mut removed_count = 0;
-    // enumerate *.deb files and remove it
-    for result in archive_dir.read_dir()? {
-      if let Ok(entry) = result {
-        if !entry.file_type()?.is_file() {
-          continue;
-        }
-        let name = entry.file_name().to_string_lossy().to_string();
-        if name.ends_with(".deb") {
-          fs::remove_file(entry.path())?;
-          removed_count += 1;
+    // enumerate *.deb files (including partially downloaded ones) and remove it
+    let partial_dir = archive_dir.join(crate::net::binary::PARTIAL_DIR);
+    for dir in [archive_dir, partial_dir.as_path()] {
+      if !dir.is_dir() {
+        continue;
+      }
+      for result in dir.read_dir()? {
+        if let Ok(entry) = result {
+          if !entry.file_type()?.is_file() {
+            continue;
+          }
+          let name = entry.file_name().to_string_lossy().to_string();
+          if name.ends_with(".deb") {
+            fs::remove_file(entry.path())?;
+            removed_count += 1;
+          }
         }
       }
     }
@@ -342,6 +683,13 @@ impl PackageClient {
   }
 }
 
+// Index of packages used to resolve dependencies.
+#[derive(Debug, Default)]
+pub struct PackageIndex {
+  pub versions: HashMap<String, Vec<PackageWithSource>>, // name -> all versions in policy order
+  pub providers: HashMap<String, Vec<PackageWithSource>>, // virtual package name -> providers
+}
+
 #[derive(Debug, Eq, Clone)]
 pub struct PackageWithSource {
   pub package: Package,
@@ -362,6 +710,186 @@ impl PartialEq for PackageWithSource {
   }
 }
 
+// installed version of `package` in `installeds`, which excludes removed ones.
+fn installed_of<'a>(installeds: &'a HashSet<Package>, package: &Package) -> Option<&'a Package> {
+  installeds.get(package).filter(|installed| {
+    matches!(&installed.status, Some(status) if status.status == DpkgStatusStatus::Installed)
+  })
+}
+
+// index packages in `index` by virtual package names which they provide.
+// Each entry is sorted by name of providers, and then descending order of version.
+fn provider_index(
+  index: &HashMap<String, Vec<PackageWithSource>>,
+) -> HashMap<String, Vec<PackageWithSource>> {
+  let mut providers: HashMap<String, Vec<PackageWithSource>> = HashMap::new();
+  for pws in index.values().flatten() {
+    for provide in &pws.package.provides {
+      providers
+        .entry(provide.package.clone())
+        .or_default()
+        .push(pws.clone());
+    }
+  }
+  for pwss in providers.values_mut() {
+    pwss.sort_by(|a, b| {
+      a.package
+        .name
+        .cmp(&b.package.name)
+        .then(b.package.version.partial_cmp(&a.package.version).unwrap())
+    });
+  }
+
+  providers
+}
+
+// Version constraint on a package collected while resolving dependencies.
+#[derive(Debug, Clone, PartialEq)]
+struct VersionConstraint {
+  depending: String, // name of depending package
+  version: VersionComp,
+}
+
+// choose a version of `versions` (sorted in descending order) which matches `spec`.
+pub fn select_pinned<'a>(
+  versions: &'a [PackageWithSource],
+  spec: &PackageSpec,
+) -> Option<&'a PackageWithSource> {
+  versions.iter().find(|pws| {
+    let version_matches = match &spec.version {
+      Some(version) => &pws.package.version == version,
+      None => true,
+    };
+    let release_matches = match &spec.release {
+      Some(release) => &pws.source.distro == release,
+      None => true,
+    };
+    version_matches && release_matches
+  })
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ResolveOptions {
+  ignore_installed: bool,   // ignore already installed packages
+  install_recommends: bool, // resolve `Recommends` as well
+}
+
+// name in the index which `dep` is resolved to.
+// Dependency on a foreign architecture is resolved to the qualified name such as `libc6:i386`,
+// unless the native package is `Multi-Arch: foreign`, which satisfies any architecture.
+fn resolve_key(dep: &Depends, index: &HashMap<String, Vec<PackageWithSource>>) -> String {
+  let arch = match dep.required_arch() {
+    Some(arch) => arch,
+    None => return dep.package.clone(),
+  };
+  match index
+    .get(&dep.package)
+    .and_then(|versions| versions.first())
+  {
+    Some(native) if native.package.multi_arch == MultiArch::Foreign => dep.package.clone(),
+    _ => format!("{}:{}", dep.package, arch),
+  }
+}
+
+// add version constraint of `dep` required by `depending`.
+// Constraints are collected for `key`, which is the name `dep` is resolved to.
+// Returns false if no new constraint is added.
+fn add_constraint(
+  constraints: &mut HashMap<String, Vec<VersionConstraint>>,
+  depending: &str,
+  key: &str,
+  dep: &Depends,
+) -> bool {
+  let version = match &dep.version {
+    Some(version) => version,
+    None => return false,
+  };
+  let constraint = VersionConstraint {
+    depending: depending.into(),
+    version: version.clone(),
+  };
+  let entry = constraints.entry(key.into()).or_default();
+  if entry.contains(&constraint) {
+    false
+  } else {
+    entry.push(constraint);
+    true
+  }
+}
+
+// Select a provider of virtual package `dep` which satisfies all constraints
+// both on the virtual package (collected for `key`) and on the provider itself.
+// Providers must be of an architecture which can satisfy `dep`.
+fn select_provider<'a>(
+  dep: &Depends,
+  key: &str,
+  index: &'a PackageIndex,
+  constraints: &HashMap<String, Vec<VersionConstraint>>,
+) -> Option<&'a PackageWithSource> {
+  let virtual_constraints = constraints.get(key).cloned().unwrap_or_default();
+  index.providers.get(&dep.package)?.iter().find(|pws| {
+    if !dep.arch_matches(&pws.package) {
+      return false;
+    }
+    // virtual package must be provided with satisfying version.
+    let provides_satisfying = virtual_constraints.iter().all(|constraint| {
+      let versioned_dep = Depends {
+        package: dep.package.clone(),
+        arch: dep.arch.clone(),
+        version: Some(constraint.version.clone()),
+        ..Default::default()
+      };
+      versioned_dep.is_satisfied_by(&pws.package)
+    });
+    // provider itself must satisfy constraints on it.
+    let self_satisfying =
+      match select_version(&pws.package.full_name(), &index.versions, constraints) {
+        Ok(Some(selected)) => selected.package.version == pws.package.version,
+        _ => false,
+      };
+    provides_satisfying && self_satisfying
+  })
+}
+
+// Select the most preferred version of `name` which satisfies all constraints on it.
+// Returns `Ok(None)` if no package named `name` exists.
+fn select_version<'a>(
+  name: &str,
+  index: &'a HashMap<String, Vec<PackageWithSource>>,
+  constraints: &HashMap<String, Vec<VersionConstraint>>,
+) -> Result<Option<&'a PackageWithSource>, PackageError> {
+  let versions = match index.get(name) {
+    Some(versions) => versions,
+    None => return Ok(None),
+  };
+  let constraints = match constraints.get(name) {
+    Some(constraints) => constraints,
+    None => return Ok(versions.first()),
+  };
+
+  match versions.iter().find(|pws| {
+    constraints
+      .iter()
+      .all(|constraint| constraint.version.matches(&pws.package.version))
+  }) {
+    Some(pws) => Ok(Some(pws)),
+    None => {
+      // report the constraint which the highest version violates.
+      let highest = &versions[0];
+      let violated = constraints
+        .iter()
+        .find(|constraint| !constraint.version.matches(&highest.package.version))
+        .unwrap_or(&constraints[0]);
+      Err(PackageError::UnresolvedDependency {
+        depended_on: name.into(),
+        depended_on_version: Box::new(highest.package.version.clone()),
+        depending_on: violated.depending.clone(),
+        depending_on_version: Box::new(violated.version.clone()),
+      })
+    }
+  }
+}
+
 pub fn to_packages(content: &str, entry_type: EntryType) -> Result<HashSet<Package>, PackageError> {
   match entry_type {
     EntryType::BINARY => parser::parse_entries_as_binary(content),
@@ -388,4 +916,67 @@ mod tests {
     let gcc = result.into_iter().next().unwrap();
     assert_eq!(gcc.name, "gcc");
   }
+
+  #[test]
+  fn test_read_all_versions() {
+    let mut client = PackageClient::new(PathBuf::from("./tests/resources/lists")).unwrap();
+    let sources: Vec<Source> = vec!["http://test5", "http://test6"]
+      .into_iter()
+      .map(|url| Source {
+        archive_type: ArchivedType::DEB,
+        url: url.into(),
+        distro: "/".into(),
+        component: crate::source::source::Component::NULL,
+        options: Default::default(),
+        mirrors: vec![],
+      })
+      .collect();
+
+    let result = client
+      .read_all_versions_with_source("jq", &sources)
+      .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].source.url, "http://test5");
+    assert_eq!(result[1].source.url, "http://test6");
+    assert!(result[0].package.version > result[1].package.version);
+  }
+
+  #[test]
+  fn test_search_by_regex() {
+    let mut client = PackageClient::new(PathBuf::from("./tests/resources/lists")).unwrap();
+    let source = Source {
+      archive_type: ArchivedType::DEB,
+      url: "http://test5".into(),
+      distro: "/".into(),
+      component: crate::source::source::Component::NULL,
+      options: Default::default(),
+      mirrors: vec![],
+    };
+
+    // match against descriptions
+    let result = client
+      .search_by_regex_with_source("json", false, vec![source.clone()])
+      .unwrap();
+    let mut names: Vec<String> = result.into_iter().map(|pws| pws.package.name).collect();
+    names.sort();
+    assert_eq!(names, vec!["jq", "libjq1"]);
+
+    // long description is also searched
+    let result = client
+      .search_by_regex_with_source("nearly all programs", false, vec![source.clone()])
+      .unwrap();
+    assert_eq!(result.len(), 1);
+
+    // match only against names
+    let result = client
+      .search_by_regex_with_source("^jq$|json", true, vec![source.clone()])
+      .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result.into_iter().next().unwrap().package.name, "jq");
+
+    // invalid regex
+    assert!(client
+      .search_by_regex_with_source("(jq", false, vec![source])
+      .is_err());
+  }
 }
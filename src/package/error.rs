@@ -29,15 +29,20 @@ pub enum PackageError {
   #[error("invalid package name is specified: {name:?}")]
   InvalidPackageName { name: String },
 
+  #[error("invalid search pattern is specified: {pattern:?}")]
+  InvalidPattern { pattern: String },
+
   #[error("Package not found: {package_name:?}")]
   PackageNotFound { package_name: String },
 
-  #[error("Failed to resolve dependencies")]
+  #[error(
+    "Failed to resolve dependencies: {depending_on:?} requires {depended_on:?} ({depending_on_version}), but available version is {depended_on_version}"
+  )]
   UnresolvedDependency {
     depended_on: String,
-    depended_on_version: Version,
+    depended_on_version: Box<Version>, // boxed to keep the error small
     depending_on: String,
-    depending_on_version: VersionComp,
+    depending_on_version: Box<VersionComp>,
   },
 
   #[error("Failed to install package")]
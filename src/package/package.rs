@@ -3,6 +3,7 @@
 */
 
 use super::version::*;
+use crate::dpkg::arch::native_arch;
 use crate::dpkg::status::DpkgStatusArea;
 
 use std::collections::HashSet;
@@ -16,6 +17,7 @@ pub struct Package {
   pub version: Version,
   pub priority: Option<Priority>,
   pub arch: String,
+  pub multi_arch: MultiArch,
   pub section: Option<String>,
   pub maintainer: String,
   pub filename: String,
@@ -23,6 +25,12 @@ pub struct Package {
   pub short_description: String,
   pub long_description: Option<String>,
   pub depends: Vec<DependsAnyOf>,
+  pub recommends: Vec<DependsAnyOf>,
+  pub suggests: Vec<DependsAnyOf>,
+  pub provides: Vec<Provides>,
+  pub conflicts: Vec<Depends>,
+  pub breaks: Vec<Depends>,
+  pub replaces: Vec<Depends>,
 
   // package information only
   pub md5: String,
@@ -34,21 +42,40 @@ pub struct Package {
   pub status: Option<DpkgStatusArea>,
 }
 
+// packages are identified by `name` and architecture qualifier, so `libc6:i386` differs from `libc6`.
+// XXX version is not considered.
 impl PartialEq for Package {
-  // XXX
   fn eq(&self, other: &Self) -> bool {
-    self.name == other.name
+    self.name == other.name && self.arch_qualifier() == other.arch_qualifier()
   }
 }
 
-// XXX use only `name` field for hashing
 impl Hash for Package {
   fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
     self.name.hash(state);
+    self.arch_qualifier().hash(state);
   }
 }
 
 impl Package {
+  // architecture which qualifies the name of this package.
+  // Native and `all` packages are not qualified, so they are identified by name only.
+  pub fn arch_qualifier(&self) -> Option<&str> {
+    match self.arch.as_str() {
+      "" | "all" => None,
+      arch if arch == native_arch() => None,
+      arch => Some(arch),
+    }
+  }
+
+  // name qualified by foreign architecture, eg) `libc6:i386`.
+  pub fn full_name(&self) -> String {
+    match self.arch_qualifier() {
+      Some(arch) => format!("{}:{}", self.name, arch),
+      None => self.name.clone(),
+    }
+  }
+
   pub fn valid(&self) -> bool {
     !self.name.is_empty()
       && self.size != 0
@@ -92,6 +119,11 @@ pub enum EntryType {
 pub enum DepType {
   Depends,
   PreDepends,
+  Recommends,
+  Suggests,
+  Conflicts,
+  Breaks,
+  Replaces,
 }
 
 impl Default for DepType {
@@ -100,9 +132,34 @@ impl Default for DepType {
   }
 }
 
+// `Multi-Arch` field, which defines how packages of different architectures interact.
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone)]
+pub enum MultiArch {
+  #[default]
+  No, // not co-installable, and satisfies dependencies only of the same arch
+  Same,    // co-installable with the same package of other architectures
+  Foreign, // satisfies dependencies of any architecture
+  Allowed, // satisfies dependencies of any architecture only if qualified with `:any`
+}
+
+impl FromStr for MultiArch {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "no" => Ok(Self::No),
+      "same" => Ok(Self::Same),
+      "foreign" => Ok(Self::Foreign),
+      "allowed" => Ok(Self::Allowed),
+      _ => Err(()),
+    }
+  }
+}
+
 #[derive(Debug, Default, Hash, PartialEq, Eq, Clone)]
 pub struct Depends {
   pub package: String,
+  pub arch: Option<String>, // architecture qualifier, such as `any`, `native` or `i386`
   pub version: Option<VersionComp>,
   pub dep_type: DepType,
 }
@@ -112,7 +169,106 @@ pub struct DependsAnyOf {
   pub depends: Vec<Depends>,
 }
 
+impl Depends {
+  // check if `package` is a target of this dependency. Version is not considered.
+  // `package` is a target also when it provides the depended-on virtual package.
+  pub fn targets(&self, package: &Package) -> bool {
+    (self.package == package.name
+      || package
+        .provides
+        .iter()
+        .any(|provide| provide.package == self.package))
+      && self.arch_matches(package)
+  }
+
+  // check if `package` satisfies this dependency.
+  // Versioned dependency is satisfied by a virtual package only if it is provided with version.
+  pub fn is_satisfied_by(&self, package: &Package) -> bool {
+    if !self.arch_matches(package) {
+      return false;
+    }
+    if self.package == package.name {
+      return match &self.version {
+        Some(version) => version.matches(&package.version),
+        None => true,
+      };
+    }
+    package
+      .provides
+      .iter()
+      .any(|provide| provide.satisfies(self))
+  }
+
+  // architecture required by this dependency. `None` means native architecture.
+  // NOTE: dependency of foreign package must be bound by `DependsAnyOf::bound_to()` first.
+  pub fn required_arch(&self) -> Option<&str> {
+    match self.arch.as_deref() {
+      None | Some("native") | Some("any") => None,
+      Some(arch) if arch == native_arch() => None,
+      Some(arch) => Some(arch),
+    }
+  }
+
+  // check if architecture of `package` can satisfy this dependency based on `Multi-Arch`.
+  pub fn arch_matches(&self, package: &Package) -> bool {
+    match package.multi_arch {
+      MultiArch::Foreign => true,
+      MultiArch::Allowed if self.arch.as_deref() == Some("any") => true,
+      _ => package.arch_qualifier() == self.required_arch(),
+    }
+  }
+}
+
+impl std::fmt::Display for Depends {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.package)?;
+    if let Some(arch) = &self.arch {
+      write!(f, ":{}", arch)?;
+    }
+    match &self.version {
+      Some(version) => write!(f, " ({})", version),
+      None => Ok(()),
+    }
+  }
+}
+
+impl std::fmt::Display for DependsAnyOf {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let depends: Vec<String> = self.depends.iter().map(|dep| dep.to_string()).collect();
+    write!(f, "{}", depends.join(" | "))
+  }
+}
+
 impl DependsAnyOf {
+  // check if `package` is a target of any of alternatives. Version is not considered.
+  pub fn targets(&self, package: &Package) -> bool {
+    self.depends.iter().any(|dep| dep.targets(package))
+  }
+
+  // check if `package` satisfies any of alternatives.
+  pub fn is_satisfied_by(&self, package: &Package) -> bool {
+    self.depends.iter().any(|dep| dep.is_satisfied_by(package))
+  }
+
+  // bind unqualified alternatives to the architecture of `depending` package,
+  // cuz dependencies of a foreign package are resolved in its architecture.
+  pub fn bound_to(&self, depending: &Package) -> Self {
+    let arch = match depending.arch_qualifier() {
+      Some(arch) => arch,
+      None => return self.clone(),
+    };
+    Self {
+      depends: self
+        .depends
+        .iter()
+        .map(|dep| Depends {
+          arch: dep.arch.clone().or_else(|| Some(arch.into())),
+          ..dep.clone()
+        })
+        .collect(),
+    }
+  }
+
   #[allow(clippy::result_unit_err)]
   pub fn from(s: &str, dep_type: DepType) -> Result<Vec<Self>, ()> {
     let mut results: Vec<Self> = vec![];
@@ -129,18 +285,21 @@ impl DependsAnyOf {
         match or_part.find('(') {
           // eg: "libc6 (> 2.14)"
           Some(ix) => {
-            let package_name = &or_part[0..ix - 1]; // eg: "libc6"
+            let (package_name, arch) = split_arch(&or_part[0..ix - 1]); // eg: "libc6"
             let version_str_tmp = &or_part[ix + 1..or_part.len() - 1]; // eg: "> 2.14""
             let depends = Depends {
-              package: package_name.into(),
+              package: package_name,
+              arch,
               version: Some(VersionComp::from(version_str_tmp).unwrap()),
               dep_type: dep_type.clone(),
             };
             any_of.push(depends);
           }
           None => {
+            let (package_name, arch) = split_arch(or_part.trim());
             let depends = Depends {
-              package: or_part.trim().into(),
+              package: package_name,
+              arch,
               version: None,
               dep_type: dep_type.clone(),
             };
@@ -156,6 +315,162 @@ impl DependsAnyOf {
   }
 }
 
+// split architecture qualifier of a package name, eg) "python3:any" -> ("python3", Some("any"))
+fn split_arch(s: &str) -> (String, Option<String>) {
+  match s.trim().split_once(':') {
+    Some((name, arch)) => (name.into(), Some(arch.into())),
+    None => (s.trim().into(), None),
+  }
+}
+
+// entry of `Provides` field, which declares a virtual package.
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone)]
+pub struct Provides {
+  pub package: String,
+  pub version: Option<Version>, // only `=` is allowed for versioned provides
+}
+
+impl Provides {
+  #[allow(clippy::result_unit_err)]
+  pub fn from(s: &str) -> Result<Vec<Self>, ()> {
+    let mut results = vec![];
+
+    for part in s.trim().split(',') {
+      let part = part.trim();
+      if part.is_empty() {
+        continue;
+      }
+      match part.find('(') {
+        // eg: "awk (= 1.3.4)"
+        Some(ix) => {
+          let package_name = part[0..ix].trim();
+          let version_comp = VersionComp::from(part[ix + 1..part.len() - 1].trim())?;
+          if version_comp.operator != VersionCompOperator::EQ {
+            return Err(());
+          }
+          results.push(Self {
+            package: package_name.into(),
+            version: Some(version_comp.version),
+          });
+        }
+        None => results.push(Self {
+          package: part.into(),
+          version: None,
+        }),
+      }
+    }
+
+    Ok(results)
+  }
+
+  // check if this virtual package satisfies `dep`.
+  pub fn satisfies(&self, dep: &Depends) -> bool {
+    if self.package != dep.package {
+      return false;
+    }
+    match (&dep.version, &self.version) {
+      (None, _) => true,
+      (Some(required), Some(provided)) => required.matches(provided),
+      (Some(_), None) => false,
+    }
+  }
+}
+
+impl std::fmt::Display for Provides {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.version {
+      Some(version) => write!(f, "{} (= {})", self.package, version),
+      None => write!(f, "{}", self.package),
+    }
+  }
+}
+
+// package requested by user, in the form of `<name>[:<arch>][=<version>|/<release>]`.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct PackageSpec {
+  pub name: String,
+  pub arch: Option<String>,     // architecture qualifier, such as "i386"
+  pub version: Option<Version>, // pinned version
+  pub release: Option<String>,  // pinned distro, such as "focal-backports"
+}
+
+impl PackageSpec {
+  #[allow(clippy::result_unit_err)]
+  pub fn from(s: &str) -> Result<Self, ()> {
+    let mut spec = Self::from_unqualified(s)?;
+    if let Some((name, arch)) = spec.name.split_once(':') {
+      if name.is_empty() || arch.is_empty() {
+        return Err(());
+      }
+      spec.arch = Some(arch.into());
+      spec.name = name.into();
+    }
+    Ok(spec)
+  }
+
+  fn from_unqualified(s: &str) -> Result<Self, ()> {
+    let s = s.trim();
+    if let Some((name, version)) = s.split_once('=') {
+      if name.is_empty() {
+        return Err(());
+      }
+      return Ok(Self {
+        name: name.into(),
+        arch: None,
+        version: Some(Version::from(version)?),
+        release: None,
+      });
+    }
+    if let Some((name, release)) = s.split_once('/') {
+      if name.is_empty() || release.is_empty() {
+        return Err(());
+      }
+      return Ok(Self {
+        name: name.into(),
+        arch: None,
+        version: None,
+        release: Some(release.into()),
+      });
+    }
+    if s.is_empty() {
+      return Err(());
+    }
+
+    Ok(Self {
+      name: s.into(),
+      arch: None,
+      version: None,
+      release: None,
+    })
+  }
+
+  pub fn is_pinned(&self) -> bool {
+    self.version.is_some() || self.release.is_some()
+  }
+
+  // name of the package in the index, which is qualified only by foreign architecture.
+  pub fn full_name(&self) -> String {
+    match self.arch.as_deref() {
+      Some(arch) if arch != native_arch() => format!("{}:{}", self.name, arch),
+      _ => self.name.clone(),
+    }
+  }
+}
+
+impl std::fmt::Display for PackageSpec {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name)?;
+    if let Some(arch) = &self.arch {
+      write!(f, ":{}", arch)?;
+    }
+    match (&self.version, &self.release) {
+      (Some(version), _) => write!(f, "={}", version),
+      (None, Some(release)) => write!(f, "/{}", release),
+      (None, None) => Ok(()),
+    }
+  }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Priority {
   REQUIRED,
@@ -181,6 +496,19 @@ impl FromStr for Priority {
   }
 }
 
+impl std::fmt::Display for Priority {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::REQUIRED => write!(f, "required"),
+      Self::IMPORTANT => write!(f, "important"),
+      Self::STANDARD => write!(f, "standard"),
+      Self::OPTIONAL => write!(f, "optional"),
+      Self::EXTRA => write!(f, "extra"),
+      Self::UNKNOWN => write!(f, "unknown"),
+    }
+  }
+}
+
 impl Default for Priority {
   fn default() -> Self {
     Self::UNKNOWN
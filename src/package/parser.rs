@@ -34,6 +34,9 @@ fn parse_entry(content: &str, entry_type: EntryType) -> Result<Package, PackageE
     }
     if parsing_description {
       if line.starts_with(' ') {
+        if !long_description.is_empty() {
+          long_description.push('\n');
+        }
         long_description += line;
         continue;
       }
@@ -63,8 +66,20 @@ fn parse_entry(content: &str, entry_type: EntryType) -> Result<Package, PackageE
 
     match section.to_lowercase().as_str() {
       "package" => package.name = ent,
-      "version" => package.version = Version::from(&ent).unwrap(),
+      "version" => {
+        package.version = match Version::from(&ent) {
+          Ok(version) => version,
+          Err(()) => {
+            return Err(PackageError::InvalidField {
+              field: section,
+              value: ent,
+            })
+          }
+        }
+      }
       "architecture" => package.arch = ent,
+      // unknown value is regarded as `no`, cuz a broken entry mustn't spoil the whole index.
+      "multi-arch" => package.multi_arch = MultiArch::from_str(&ent).unwrap_or_default(),
       "priority" => package.priority = Some(Priority::from_str(&ent).unwrap()),
       "section" => package.section = Some(ent),
       "maintainer" => package.maintainer = ent,
@@ -96,12 +111,41 @@ fn parse_entry(content: &str, entry_type: EntryType) -> Result<Package, PackageE
       "pre-depends" => package
         .depends
         .extend(DependsAnyOf::from(&ent, DepType::PreDepends).unwrap()),
+      "recommends" => package
+        .recommends
+        .extend(DependsAnyOf::from(&ent, DepType::Recommends).unwrap()),
+      "suggests" => package
+        .suggests
+        .extend(DependsAnyOf::from(&ent, DepType::Suggests).unwrap()),
+      "conflicts" => package
+        .conflicts
+        .extend(parse_relations(&ent, DepType::Conflicts)),
+      "breaks" => package
+        .breaks
+        .extend(parse_relations(&ent, DepType::Breaks)),
+      "replaces" => package
+        .replaces
+        .extend(parse_relations(&ent, DepType::Replaces)),
+      // broken `Provides` is just dropped for the entry.
+      "provides" => {
+        if let Ok(provides) = Provides::from(&ent) {
+          package.provides.extend(provides);
+        }
+      }
       "status" => package.status = Some(DpkgStatusArea::from(&ent)),
       "files" | "checksums-sha1" | "checksums-sha256" | "package-list" => parsing_unknown = true,
       _ => continue,
     }
   }
 
+  // flush multi-line fields which end with the entry itself.
+  if parsing_description && !long_description.is_empty() {
+    package.long_description = Some(long_description);
+  }
+  if parsing_conffile {
+    package.conffiles = conffiles;
+  }
+
   let is_valid = match entry_type {
     EntryType::BINARY => package.valid(),
     EntryType::STATUS => package.valid_as_status(),
@@ -118,6 +162,15 @@ fn parse_entry(content: &str, entry_type: EntryType) -> Result<Package, PackageE
   }
 }
 
+// parse relation fields which don't allow alternatives, such as `Conflicts`.
+fn parse_relations(s: &str, dep_type: DepType) -> Vec<Depends> {
+  DependsAnyOf::from(s, dep_type)
+    .unwrap()
+    .into_iter()
+    .flat_map(|anyof| anyof.depends)
+    .collect()
+}
+
 pub fn parse_entries_as_binary(entries: &str) -> Result<HashSet<Package>, PackageError> {
   do_parse_entries(entries, EntryType::BINARY)
 }
@@ -130,16 +183,33 @@ pub fn parse_entries_as_status(entries: &str) -> Result<HashSet<Package>, Packag
   do_parse_entries(entries, EntryType::STATUS)
 }
 
+// every entry is kept, even if an index lists several versions of a package.
+pub fn parse_all_entries_as_binary(entries: &str) -> Result<Vec<Package>, PackageError> {
+  do_parse_all_entries(entries, EntryType::BINARY)
+}
+
+// the first entry is kept for each package.
 fn do_parse_entries(
   entries: &str,
   entry_type: EntryType,
 ) -> Result<HashSet<Package>, PackageError> {
+  Ok(
+    do_parse_all_entries(entries, entry_type)?
+      .into_iter()
+      .collect(),
+  )
+}
+
+fn do_parse_all_entries(
+  entries: &str,
+  entry_type: EntryType,
+) -> Result<Vec<Package>, PackageError> {
   let blocks = split_by_empty_line(entries);
   let entries: Vec<String> = blocks.into_iter().map(|block| block.join("\n")).collect();
-  let mut packages = HashSet::new();
+  let mut packages = vec![];
 
   for entry in &entries {
-    packages.insert(parse_entry(entry, entry_type.clone())?);
+    packages.push(parse_entry(entry, entry_type.clone())?);
   }
 
   Ok(packages)
@@ -194,6 +264,7 @@ mod tests {
         DependsAnyOf {
           depends: vec![Depends {
             package: "vim-common".into(),
+            arch: None,
             version: Some(VersionComp {
               version: Version::from("2:8.1.2269-1ubuntu5").unwrap(),
               operator: version::VersionCompOperator::EQ,
@@ -204,6 +275,7 @@ mod tests {
         DependsAnyOf {
           depends: vec![Depends {
             package: "vim-runtime".into(),
+            arch: None,
             version: Some(VersionComp {
               version: Version::from("2:8.1.2269-1ubuntu5").unwrap(),
               operator: version::VersionCompOperator::EQ,
@@ -214,6 +286,7 @@ mod tests {
         DependsAnyOf {
           depends: vec![Depends {
             package: "libacl1".into(),
+            arch: None,
             version: Some(VersionComp {
               version: Version::from("2.2.23").unwrap(),
               operator: version::VersionCompOperator::GE,
@@ -222,10 +295,64 @@ mod tests {
           }],
         },
       ],
+      provides: vec![Provides {
+        package: "editor".into(),
+        version: None,
+      }],
       ..Default::default()
     };
 
     let package = parse_entry(entry_str.trim(), EntryType::BINARY).unwrap();
     assert_eq!(answer, package);
+    assert_eq!(answer.provides, package.provides);
+    assert_eq!(package.suggests.len(), 3);
+    assert_eq!(package.suggests[0].depends[0].package, "ctags");
+    assert_eq!(package.suggests[0].depends[0].dep_type, DepType::Suggests);
+
+    // versioned provides
+    let provides = Provides::from("awk (= 1.3.4), mawk-virtual").unwrap();
+    assert_eq!(provides.len(), 2);
+    assert_eq!(provides[0].package, "awk");
+    assert_eq!(provides[0].version, Some(Version::from("1.3.4").unwrap()));
+    assert_eq!(provides[1].version, None);
+    assert!(Provides::from("awk (>= 1.3.4)").is_err());
+  }
+
+  #[test]
+  fn test_parse_multiarch() {
+    let entry_str = "Package: python3-foo
+Architecture: i386
+Multi-Arch: same
+Depends: python3:any (>= 3.8), libc6, libbar:amd64
+Size: 1
+Filename: hoge
+Maintainer: a";
+    let package = parse_entry(entry_str, EntryType::BINARY).unwrap();
+    assert_eq!(package.multi_arch, MultiArch::Same);
+    let archs: Vec<Option<String>> = package
+      .depends
+      .iter()
+      .map(|anyof| anyof.depends[0].arch.clone())
+      .collect();
+    assert_eq!(archs, vec![Some("any".into()), None, Some("amd64".into())]);
+    assert_eq!(package.depends[0].depends[0].package, "python3");
+    assert!(package.depends[0]
+      .to_string()
+      .starts_with("python3:any (>= "));
+
+    // unqualified dependency of foreign package is bound to its architecture.
+    let libc6 = package.depends[1].bound_to(&package);
+    assert_eq!(libc6.depends[0].arch.as_deref(), package.arch_qualifier());
+
+    // invalid fields are ignored only for the entry.
+    let package = parse_entry(
+      &entry_str
+        .replace("Multi-Arch: same", "Multi-Arch: hoge")
+        .replace("Size: 1", "Provides: foo (>= 1.0)\nSize: 1"),
+      EntryType::BINARY,
+    )
+    .unwrap();
+    assert_eq!(package.multi_arch, MultiArch::No);
+    assert!(package.provides.is_empty());
   }
 }
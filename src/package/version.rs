@@ -27,7 +27,7 @@ static CHILDA_PSEUDO_ASCII_CODE: u8 = 0x0;
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone)]
 pub struct Version {
-  epoch: u64, // XXX not sure it is used when ordering and comparision.
+  epoch: u64,
   upstream_version: String,
   debian_revision: String,
 }
@@ -35,26 +35,36 @@ pub struct Version {
 impl Version {
   #[allow(clippy::result_unit_err)]
   pub fn from(s: &str) -> Result<Self, ()> {
-    match s.rfind('-') {
-      Some(last_hyphen) => match s.find(':') {
-        Some(colon_ix) => Ok(Self {
-          epoch: s[0..colon_ix].to_string().parse().unwrap(),
-          upstream_version: s[colon_ix + 1..last_hyphen].into(),
-          debian_revision: s[last_hyphen + 1..].into(),
-        }),
-        None => Ok(Self {
-          epoch: 0,
-          upstream_version: s[..last_hyphen].into(),
-          debian_revision: s[last_hyphen + 1..].into(),
-        }),
-      },
+    let (epoch, rest) = match s.split_once(':') {
+      Some((epoch, rest)) => (epoch.parse().map_err(|_| ())?, rest),
+      None => (0, s),
+    };
+    match rest.rfind('-') {
+      Some(last_hyphen) => Ok(Self {
+        epoch,
+        upstream_version: rest[..last_hyphen].into(),
+        debian_revision: rest[last_hyphen + 1..].into(),
+      }),
       None => Ok(Self {
-        epoch: 0,
-        upstream_version: s.into(),
+        epoch,
+        upstream_version: rest.into(),
         debian_revision: String::new(),
       }),
     }
   }
+
+  // version string as written in Packages file, which omits zero epoch and empty revision.
+  pub fn to_raw_string(&self) -> String {
+    let mut result = String::new();
+    if self.epoch != 0 {
+      result += &format!("{}:", self.epoch);
+    }
+    result += &self.upstream_version;
+    if !self.debian_revision.is_empty() {
+      result += &format!("-{}", self.debian_revision);
+    }
+    result
+  }
 }
 
 impl std::fmt::Display for Version {
@@ -79,6 +89,10 @@ impl Default for Version {
 
 impl PartialOrd for Version {
   fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+    match self.epoch.cmp(&other.epoch) {
+      cmp::Ordering::Equal => {}
+      ord => return Some(ord),
+    }
     match compare_version(&self.upstream_version, &other.upstream_version) {
       core::cmp::Ordering::Equal => {}
       ord => return Some(ord),
@@ -191,8 +205,7 @@ impl VersionComp {
     }
   }
 
-  #[allow(dead_code)]
-  fn matches(&self, other: &Version) -> bool {
+  pub fn matches(&self, other: &Version) -> bool {
     use VersionCompOperator::*;
     match self.operator {
       GT => &self.version < other,
@@ -221,8 +234,8 @@ impl std::fmt::Display for VersionCompOperator {
       Self::GT => write!(f, ">"),
       Self::GE => write!(f, ">="),
       Self::EQ => write!(f, "="),
-      Self::LT => write!(f, ">"),
-      Self::LE => write!(f, ">="),
+      Self::LT => write!(f, "<"),
+      Self::LE => write!(f, "<="),
       Self::ANY => write!(f, "<>"),
     }
   }
@@ -319,6 +332,11 @@ mod tests {
     assert_eq!(Version::from(v1_str).is_ok(), true);
     assert_eq!(Version::from(v2_str).is_ok(), true);
     assert_eq!(Version::from(v3_str).is_ok(), true);
+
+    // epoch is parsed without revision, and invalid one is an error.
+    assert_eq!(Version::from("2:8.1").unwrap().to_raw_string(), "2:8.1");
+    assert!(Version::from("2:8.1").unwrap() > Version::from("8.2").unwrap());
+    assert!(Version::from("a:1-1").is_err());
   }
 
   #[test]
@@ -336,6 +354,11 @@ mod tests {
     assert_eq!(v1 == v2, true);
     assert_eq!(v3 < v4 && v4 < v5 && v5 < v6 && v6 < v7, true);
     assert_eq!(v8 < v9, true);
+
+    // epoch takes precedence over upstream version
+    let v10 = Version::from("1:1.0-1").unwrap();
+    let v11 = Version::from("9.0-1").unwrap();
+    assert_eq!(v11 < v10, true);
   }
 
   #[test]
@@ -1,5 +1,6 @@
 pub mod client;
 pub mod error;
 mod parser;
+pub mod release;
 #[allow(clippy::module_inception)]
 pub mod source;
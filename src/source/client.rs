@@ -3,6 +3,7 @@
 */
 
 use super::{error::SourceError, parser, source::*};
+use crate::dpkg::arch::Architectures;
 
 use itertools::Itertools;
 use std::collections::HashSet;
@@ -37,9 +38,14 @@ impl SourceClient {
       });
     }
 
-    let content = fs::read_to_string(path)?;
+    let content = fs::read_to_string(&path)?;
 
-    parser::parse_lines(&content)
+    // `*.sources` is written in deb822 format, others are one-line format.
+    if path.extension().and_then(|ext| ext.to_str()) == Some("sources") {
+      parser::parse_deb822(&content)
+    } else {
+      parser::parse_lines(&content)
+    }
   }
 
   // search source directory and read all files
@@ -53,6 +59,17 @@ impl SourceClient {
     Ok(sources.into_iter().flatten().unique().collect())
   }
 
+  // read all sources, and split them into sources of each architecture in `archs`.
+  pub fn read_all_for(&self, archs: &Architectures) -> Result<HashSet<Source>, SourceError> {
+    Ok(
+      self
+        .read_all()?
+        .iter()
+        .flat_map(|source| source.per_arch(archs))
+        .collect(),
+    )
+  }
+
   // find and return candidate list files.
   fn find_candidates(&self) -> Vec<PathBuf> {
     let mut target_pathes = vec![];
@@ -69,7 +86,11 @@ impl SourceClient {
       let candidates = fs::read_dir(sources_list_d_path).unwrap();
       for ent in candidates.flatten() {
         let path = ent.path();
-        if path.is_file() && path.extension().is_some() && path.extension().unwrap() == "list" {
+        let is_candidate = matches!(
+          path.extension().and_then(|ext| ext.to_str()),
+          Some("list") | Some("sources")
+        );
+        if path.is_file() && is_candidate {
           target_pathes.push(path);
         }
       }
@@ -7,14 +7,26 @@ use super::{error::SourceError, source::Source, source::*};
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
 
 fn parse_line(line: &str) -> Result<HashSet<Source>, SourceError> {
   assert!(!line.contains('\n'));
 
-  // ignore architecture specifier: eg) [arch=amd64]
+  // options: eg) [arch=amd64 signed-by=/usr/share/keyrings/hoge.gpg]
   lazy_static! {
-    static ref RE: Regex = Regex::new(r"\[.*\]\s").unwrap();
+    static ref RE: Regex = Regex::new(r"\[(.*)\]\s").unwrap();
+  }
+  let mut options = SourceOptions::default();
+  if let Some(captures) = RE.captures(line) {
+    for option in captures[1].split_whitespace() {
+      let (key, value) = match option.split_once('=') {
+        Some(kv) => kv,
+        None => return Err(SourceError::InvalidFormat { msg: line.into() }),
+      };
+      options.set(key, value)?;
+    }
   }
   let line = &RE.replace_all(line, "").to_string();
 
@@ -49,7 +61,7 @@ fn parse_line(line: &str) -> Result<HashSet<Source>, SourceError> {
     }
   };
 
-  let url: String = parts[1].into();
+  let (url, mirrors) = parse_uri(parts[1])?;
   let distro: String = parts[2].into();
   let mut components = vec![];
 
@@ -66,9 +78,8 @@ fn parse_line(line: &str) -> Result<HashSet<Source>, SourceError> {
       };
       components.push(component);
     }
-  } else {
-    components.push(Component::NULL);
   }
+  let components = check_flat(&distro, components, line)?;
 
   Ok(
     components
@@ -78,11 +89,59 @@ fn parse_line(line: &str) -> Result<HashSet<Source>, SourceError> {
         url: url.clone(),
         distro: distro.clone(),
         component: component.clone(),
+        options: options.clone(),
+        mirrors: mirrors.clone(),
       })
       .collect(),
   )
 }
 
+// Flat repository is given by a suite of exact path ending with `/` (eg. `./`) without components,
+// which is represented by `Component::NULL`. Otherwise, at least one component is required.
+fn check_flat(
+  suite: &str,
+  components: Vec<Component>,
+  entry: &str,
+) -> Result<Vec<Component>, SourceError> {
+  match (suite.ends_with('/'), components.is_empty()) {
+    (true, true) => Ok(vec![Component::NULL]),
+    (false, false) => Ok(components),
+    _ => Err(SourceError::InvalidFormat { msg: entry.into() }),
+  }
+}
+
+// returns primary URL and fallback mirrors of the URI.
+// `mirror+file:` gives a list of mirrors. The first one is used as primary URL.
+fn parse_uri(uri: &str) -> Result<(String, Vec<String>), SourceError> {
+  match uri.strip_prefix("mirror+file:") {
+    Some(path) => {
+      let mut mirrors = read_mirror_list(Path::new(path))?;
+      Ok((mirrors.remove(0), mirrors))
+    }
+    None => Ok((uri.to_string(), vec![])),
+  }
+}
+
+// read mirror list of `mirror+file:`, which has a URL of mirror per line.
+// Metadata following the URL and comments are ignored.
+fn read_mirror_list(path: &Path) -> Result<Vec<String>, SourceError> {
+  let content = fs::read_to_string(path).map_err(|_| SourceError::FileNotFound {
+    target: path.to_string_lossy().to_string(),
+  })?;
+  let mirrors: Vec<String> = content
+    .lines()
+    .map(|line| line.split('#').next().unwrap().trim())
+    .filter_map(|line| line.split_whitespace().next())
+    .map(String::from)
+    .collect();
+  if mirrors.is_empty() {
+    return Err(SourceError::InvalidFormat {
+      msg: format!("no mirror is listed in {}", path.to_string_lossy()),
+    });
+  }
+  Ok(mirrors)
+}
+
 pub fn parse_lines(content: &str) -> Result<HashSet<Source>, SourceError> {
   let mut sources = HashSet::new();
   for line in content.lines() {
@@ -97,9 +156,152 @@ pub fn parse_lines(content: &str) -> Result<HashSet<Source>, SourceError> {
   Ok(sources)
 }
 
+// parse deb822 format `.sources` file, which consists of stanzas separated by empty lines.
+// eg)
+//  Types: deb deb-src
+//  URIs: http://jp.archive.ubuntu.com/ubuntu/
+//  Suites: focal focal-updates
+//  Components: main restricted
+//  Signed-By: /usr/share/keyrings/ubuntu-archive-keyring.gpg
+pub fn parse_deb822(content: &str) -> Result<HashSet<Source>, SourceError> {
+  let mut sources = HashSet::new();
+  let mut stanza: Vec<(String, String)> = vec![];
+  for line in content.lines().chain(std::iter::once("")) {
+    if line.starts_with('#') {
+      continue;
+    }
+    if line.trim().is_empty() {
+      if !stanza.is_empty() {
+        sources.extend(parse_stanza(&stanza)?);
+        stanza.clear();
+      }
+      continue;
+    }
+    // continuation line of multi-line field.
+    if line.starts_with(' ') || line.starts_with('\t') {
+      match stanza.last_mut() {
+        Some((_, value)) => {
+          value.push('\n');
+          value.push_str(line.trim());
+        }
+        None => return Err(SourceError::InvalidFormat { msg: line.into() }),
+      }
+      continue;
+    }
+    match line.split_once(':') {
+      Some((field, value)) => stanza.push((field.trim().to_lowercase(), value.trim().into())),
+      None => return Err(SourceError::InvalidFormat { msg: line.into() }),
+    }
+  }
+
+  Ok(sources)
+}
+
+fn parse_stanza(stanza: &[(String, String)]) -> Result<HashSet<Source>, SourceError> {
+  let field = |name: &str| {
+    stanza
+      .iter()
+      .find(|(field, _)| field == name)
+      .map(|(_, value)| value.as_str())
+  };
+  let required = |name: &str| {
+    field(name).ok_or_else(|| SourceError::InvalidFormat {
+      msg: format!("`{}` is missing in deb822 stanza", name),
+    })
+  };
+
+  if let Some(enabled) = field("enabled") {
+    match enabled {
+      "yes" => {}
+      "no" => return Ok(HashSet::new()),
+      _ => {
+        return Err(SourceError::InvalidField {
+          field: "Enabled".into(),
+          value: enabled.into(),
+        })
+      }
+    }
+  }
+
+  let mut archive_types = vec![];
+  for type_str in required("types")?.split_whitespace() {
+    match ArchivedType::from_str(type_str) {
+      Ok(t) => archive_types.push(t),
+      Err(()) => {
+        return Err(SourceError::InvalidField {
+          field: "Types".into(),
+          value: type_str.into(),
+        })
+      }
+    }
+  }
+  let uris: Vec<&str> = required("uris")?.split_whitespace().collect();
+  let suites: Vec<&str> = required("suites")?.split_whitespace().collect();
+  let mut components = vec![];
+  for component_str in field("components").unwrap_or("").split_whitespace() {
+    match Component::from_str(component_str) {
+      Ok(c) => components.push(c),
+      Err(()) => {
+        return Err(SourceError::InvalidField {
+          field: "Components".into(),
+          value: component_str.into(),
+        })
+      }
+    }
+  }
+  // XXX keys embedded in `Signed-By` are not supported now.
+  // Such a stanza is skipped, cuz it mustn't spoil other sources nor be verified by other keys.
+  if let Some(value) = field("signed-by") {
+    if value.contains('\n') || value.starts_with("-----BEGIN") {
+      eprintln!(
+        "Warning: skipped source of {}, cuz keys embedded in `Signed-By` are not supported.",
+        uris.join(" ")
+      );
+      return Ok(HashSet::new());
+    }
+  }
+  // options corresponding to ones of one-line format, whose lists are separated by spaces.
+  let mut options = SourceOptions::default();
+  for (name, key) in [
+    ("architectures", "arch"),
+    ("signed-by", "signed-by"),
+    ("trusted", "trusted"),
+    ("languages", "lang"),
+    ("targets", "target"),
+  ] {
+    if let Some(value) = field(name) {
+      let value = value.split_whitespace().collect::<Vec<&str>>().join(",");
+      options.set(key, &value)?;
+    }
+  }
+
+  let mut sources = HashSet::new();
+  for uri in uris {
+    let (url, mirrors) = parse_uri(uri)?;
+    for archive_type in &archive_types {
+      for suite in &suites {
+        let entry = format!("Suites: {}", suite);
+        for component in &check_flat(suite, components.clone(), &entry)? {
+          sources.insert(Source {
+            archive_type: archive_type.clone(),
+            url: url.clone(),
+            distro: suite.to_string(),
+            component: component.clone(),
+            options: options.clone(),
+            mirrors: mirrors.clone(),
+          });
+        }
+      }
+    }
+  }
+
+  Ok(sources)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
+  use std::path::PathBuf;
 
   #[test]
   fn parse_single_line() {
@@ -125,6 +327,38 @@ mod tests {
     let parsed = parse_line(line).unwrap();
     assert_eq!(answer, parsed);
 
+    // check if options are parsed
+    let line = "deb [arch=i386,amd64 signed-by=/usr/share/keyrings/ubuntu.gpg trusted=yes lang=en,ja] http://jp.archive.ubuntu.com/ubuntu/ focal main restricted";
+    let parsed = parse_line(line).unwrap();
+    assert_eq!(answer.len(), parsed.len());
+    for source in &parsed {
+      assert_eq!(
+        source.options,
+        SourceOptions {
+          arch: Some(vec!["i386".into(), "amd64".into()]),
+          signed_by: Some(PathBuf::from("/usr/share/keyrings/ubuntu.gpg")),
+          trusted: Some(true),
+          lang: Some(vec!["en".into(), "ja".into()]),
+          target: None,
+        }
+      );
+      assert!(source.is_trusted());
+      assert!(source.packages_url().contains("/binary-i386/"));
+    }
+    let line = "deb [trusted=maybe] http://jp.archive.ubuntu.com/ubuntu/ focal main";
+    assert!(parse_line(line).is_err());
+
+    // check if `mirror+file:` is resolved into primary URL and fallback mirrors
+    let line = "deb mirror+file:./tests/resources/mirrors/mirrors.txt focal main restricted";
+    let parsed = parse_line(line).unwrap();
+    assert_eq!(answer.len(), parsed.len());
+    for source in &parsed {
+      assert_eq!(source.url, "http://jp.archive.ubuntu.com/ubuntu/");
+      assert_eq!(source.mirrors, vec!["http://archive.ubuntu.com/ubuntu/"]);
+    }
+    let line = "deb mirror+file:./tests/resources/mirrors/not-exist.txt focal main";
+    assert!(parse_line(line).is_err());
+
     // check if invalid line can't be parsed
     let line = "deb http://jp.archive.ubuntu.com/ubuntu/ # focal main restricted";
     let parsed = parse_line(line);
@@ -171,4 +405,94 @@ mod tests {
     let sources = parse_lines(lines).unwrap();
     assert_eq!(answers_set, sources);
   }
+
+  #[test]
+  fn parse_deb822_stanzas() {
+    let content = "Types: deb deb-src
+URIs: http://jp.archive.ubuntu.com/ubuntu/
+Suites: focal focal-updates
+Components: main
+Signed-By: /usr/share/keyrings/ubuntu.gpg
+
+# comment between stanzas
+Types: deb
+URIs: https://download.example.com/repo/
+Suites: ./
+Architectures: arm64
+
+Types: deb
+URIs: http://example.com/disabled/
+Suites: focal
+Enabled: no
+";
+    let parsed = parse_deb822(content).unwrap();
+    assert_eq!(parsed.len(), 5);
+    assert_eq!(
+      parsed
+        .iter()
+        .filter(|source| source.options.signed_by
+          == Some(PathBuf::from("/usr/share/keyrings/ubuntu.gpg")))
+        .count(),
+      4
+    );
+    assert!(parsed.contains(&Source {
+      archive_type: ArchivedType::DEB,
+      url: "https://download.example.com/repo/".into(),
+      distro: "./".into(),
+      component: Component::NULL,
+      options: SourceOptions {
+        arch: Some(vec!["arm64".into()]),
+        ..Default::default()
+      },
+      mirrors: vec![],
+    }));
+
+    // required fields must exist
+    assert!(parse_deb822("Types: deb\nSuites: focal\n").is_err());
+
+    // stanza with embedded key is skipped, cuz it's not supported
+    let content = "Types: deb
+URIs: http://example.com/
+Suites: focal
+Signed-By:
+ -----BEGIN PGP PUBLIC KEY BLOCK-----
+ .
+ mQINBF
+ -----END PGP PUBLIC KEY BLOCK-----
+";
+    assert!(parse_deb822(content).unwrap().is_empty());
+  }
+
+  #[test]
+  fn parse_components_and_flat() {
+    // components not known to rapt2 are kept as they are.
+    let line = "deb http://deb.debian.org/debian bookworm main non-free non-free-firmware";
+    let parsed = parse_line(line).unwrap();
+    assert_eq!(parsed.len(), 3);
+    let source = parsed
+      .iter()
+      .find(|source| source.component == Component::Other("non-free-firmware".into()))
+      .unwrap();
+    assert!(!source.is_flat());
+    assert_eq!(
+      source.packages_index_path(),
+      format!("non-free-firmware/binary-{}/Packages", source.arch())
+    );
+    assert!(source
+      .packages_url()
+      .starts_with("http://deb.debian.org/debian/dists/bookworm/non-free-firmware/"));
+
+    // flat repository is given only by suite ending with `/`.
+    let parsed = parse_line("deb http://example.com/repo ./").unwrap();
+    let source = parsed.iter().next().unwrap();
+    assert!(source.is_flat());
+    assert_eq!(
+      source.inrelease_url(),
+      "http://example.com/repo/./InRelease"
+    );
+    assert_eq!(source.packages_index_path(), "Packages");
+    assert!(parse_line("deb http://example.com/repo focal").is_err());
+    assert!(parse_line("deb http://example.com/repo ./ main").is_err());
+    assert!(parse_deb822("Types: deb\nURIs: http://example.com/\nSuites: focal\n").is_err());
+  }
 }
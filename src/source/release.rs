@@ -0,0 +1,149 @@
+/*
+ This file implements parse of Release/InRelease file of a repository.
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// meta information of a distribution, which is used to match `Pin: release`
+// and to verify indices listed in it.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Release {
+  pub origin: Option<String>,
+  pub label: Option<String>,
+  pub suite: Option<String>,
+  pub codename: Option<String>,
+  pub version: Option<String>,
+  pub architectures: Vec<String>, // architectures built in the dist
+  pub files: HashMap<String, ReleaseFile>, // path relative to the dist => entry in `SHA256`
+}
+
+// an index file listed in `SHA256` field of Release.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReleaseFile {
+  pub size: u64,
+  pub sha256: String,
+}
+
+impl Release {
+  // parse header fields and `SHA256` list of Release/InRelease.
+  // Signature and other checksum lists of InRelease are just ignored.
+  pub fn from(content: &str) -> Self {
+    let mut release = Self::default();
+    let mut in_sha256 = false;
+
+    for line in content.lines() {
+      if line.starts_with("-----BEGIN PGP SIGNATURE-----") {
+        break;
+      }
+      // continuation lines of multi-line fields.
+      if line.starts_with(' ') || line.starts_with('\t') {
+        if in_sha256 {
+          if let Some((path, file)) = parse_file_line(line) {
+            release.files.insert(path, file);
+          }
+        }
+        continue;
+      }
+      let (field, value) = match line.split_once(':') {
+        Some((field, value)) => (field.trim(), value.trim().to_string()),
+        None => continue,
+      };
+      in_sha256 = false;
+      match field.to_lowercase().as_str() {
+        "origin" => release.origin = Some(value),
+        "label" => release.label = Some(value),
+        "suite" => release.suite = Some(value),
+        "codename" => release.codename = Some(value),
+        "version" => release.version = Some(value),
+        "architectures" => {
+          release.architectures = value.split_whitespace().map(String::from).collect()
+        }
+        "sha256" => in_sha256 = true,
+        _ => continue,
+      }
+    }
+
+    release
+  }
+
+  // get an entry of the index whose path is relative to the dist, eg) `main/binary-amd64/Packages.gz`.
+  pub fn file(&self, path: &str) -> Option<&ReleaseFile> {
+    self.files.get(path)
+  }
+
+  // check if packages of `arch` are built in the dist.
+  // Every architecture is regarded as supported if `Architectures` field is missing.
+  pub fn supports_arch(&self, arch: &str) -> bool {
+    self.architectures.is_empty() || self.architectures.iter().any(|a| a == arch)
+  }
+
+  // read cached Release/InRelease. Returns `None` if it isn't cached yet.
+  pub fn read(path: &Path) -> Option<Self> {
+    fs::read_to_string(path)
+      .ok()
+      .map(|content| Self::from(&content))
+  }
+}
+
+// parse a line of checksum list: `<hash> <size> <path>`
+fn parse_file_line(line: &str) -> Option<(String, ReleaseFile)> {
+  let parts: Vec<&str> = line.split_whitespace().collect();
+  if parts.len() != 3 {
+    return None;
+  }
+  let size = parts[1].parse().ok()?;
+  Some((
+    parts[2].to_string(),
+    ReleaseFile {
+      size,
+      sha256: parts[0].to_lowercase(),
+    },
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_release() {
+    let content = "-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA512
+
+Origin: Ubuntu
+Label: Ubuntu
+Suite: focal-backports
+Version: 20.04
+Codename: focal
+Architectures: amd64 arm64
+MD5Sum:
+ 7a1e4d7c7d1f1d42ef0c0d8e8a1b4cd1        1 main/binary-amd64/Packages
+SHA256:
+ e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855        0 main/binary-amd64/Packages
+ 2f5c2a1ee9f3c3c7a3c4a1e3a1b5b3b0c3f0b1f5a8c5d6e6f2a3b4c5d6e7f8a9     1234 main/binary-amd64/Packages.gz
+-----BEGIN PGP SIGNATURE-----
+
+iQIzBAEBCgAdFiEE
+-----END PGP SIGNATURE-----
+";
+    let release = Release::from(content);
+    assert_eq!(release.origin, Some("Ubuntu".into()));
+    assert_eq!(release.label, Some("Ubuntu".into()));
+    assert_eq!(release.suite, Some("focal-backports".into()));
+    assert_eq!(release.codename, Some("focal".into()));
+    assert_eq!(release.version, Some("20.04".into()));
+    assert_eq!(release.files.len(), 2);
+    assert!(release.supports_arch("arm64"));
+    assert!(!release.supports_arch("i386"));
+    assert_eq!(
+      release.file("main/binary-amd64/Packages.gz"),
+      Some(&ReleaseFile {
+        size: 1234,
+        sha256: "2f5c2a1ee9f3c3c7a3c4a1e3a1b5b3b0c3f0b1f5a8c5d6e6f2a3b4c5d6e7f8a9".into(),
+      })
+    );
+    assert_eq!(release.file("main/binary-i386/Packages.gz"), None);
+  }
+}
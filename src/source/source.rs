@@ -2,8 +2,12 @@
  This file defines `sources.list` entry.
 */
 
+use super::error::SourceError;
+use crate::dpkg::arch::{native_arch, Architectures};
+
 use std::collections::HashSet;
 use std::hash::Hash;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 // archive type of the source.
@@ -25,6 +29,8 @@ impl FromStr for ArchivedType {
   }
 }
 
+// component of a distribution. Well-known ones are listed for convenience,
+// and any other name such as `non-free-firmware` is kept as `Other`.
 #[derive(PartialEq, Debug, Eq, Hash, Clone, PartialOrd, Ord)]
 pub enum Component {
   MAIN,       // free software, fully supported by Ubuntu
@@ -34,7 +40,8 @@ pub enum Component {
   PARTNER,    // Canonical partners
   CONTRIB,    // DFSG-compliant, but have deps not in main.
   STABLE,
-  NULL, // component is not given (refer to /docs/Source.md)
+  Other(String), // component not listed above
+  NULL,          // component is not given, ie) flat repository (refer to /docs/Source.md)
 }
 
 impl std::fmt::Display for Component {
@@ -47,6 +54,7 @@ impl std::fmt::Display for Component {
       Self::PARTNER => write!(f, "partner"),
       Self::CONTRIB => write!(f, "contrib"),
       Self::STABLE => write!(f, "stable"),
+      Self::Other(name) => write!(f, "{}", name),
       Self::NULL => write!(f, "(empty)"),
     }
   }
@@ -64,7 +72,8 @@ impl FromStr for Component {
       "partner" => Ok(Self::PARTNER),
       "contrib" => Ok(Self::CONTRIB),
       "stable" => Ok(Self::STABLE),
-      _ => Ok(Self::NULL),
+      "" => Ok(Self::NULL),
+      name => Ok(Self::Other(name.into())),
     }
   }
 }
@@ -76,6 +85,47 @@ pub struct Source {
   pub url: String,
   pub distro: String,
   pub component: Component,
+  pub options: SourceOptions, // options given as `[key=value ...]`
+  pub mirrors: Vec<String>,   // fallback mirrors tried in order when `url` fails
+}
+
+// options of sources.list entry, eg) `deb [arch=amd64,i386 signed-by=/path/to/key.gpg] ...`
+// `lang` and `target` are just kept because translations and other targets are not fetched now.
+#[derive(Debug, Default, Eq, Clone, PartialEq, Hash)]
+pub struct SourceOptions {
+  pub arch: Option<Vec<String>>,   // architectures to fetch
+  pub signed_by: Option<PathBuf>,  // keyring which `InRelease` must be signed by
+  pub trusted: Option<bool>,       // if true, `InRelease` is trusted without signature check
+  pub lang: Option<Vec<String>>,   // languages of translations
+  pub target: Option<Vec<String>>, // index targets to fetch
+}
+
+impl SourceOptions {
+  // set an option of `key=value`.
+  // XXX unknown options and modifiers such as `arch+=` are ignored now.
+  pub fn set(&mut self, key: &str, value: &str) -> Result<(), SourceError> {
+    let list = || Some(value.split(',').map(String::from).collect::<Vec<String>>());
+    match key {
+      "arch" => self.arch = list(),
+      "signed-by" => self.signed_by = Some(PathBuf::from(value)),
+      "trusted" => {
+        self.trusted = match value {
+          "yes" => Some(true),
+          "no" => Some(false),
+          _ => {
+            return Err(SourceError::InvalidField {
+              field: key.into(),
+              value: value.into(),
+            })
+          }
+        }
+      }
+      "lang" => self.lang = list(),
+      "target" => self.target = list(),
+      _ => {}
+    }
+    Ok(())
+  }
 }
 
 impl Source {
@@ -92,10 +142,69 @@ impl Source {
         url: url.into(),
         distro: distro.into(),
         component: component.clone(),
+        options: SourceOptions::default(),
+        mirrors: vec![],
+      })
+      .collect()
+  }
+
+  // architecture of binary packages to fetch.
+  // NOTE: only the first one is used. Use `per_arch()` to fetch all of them.
+  pub fn arch(&self) -> &str {
+    match &self.options.arch {
+      Some(archs) if !archs.is_empty() => &archs[0],
+      _ => native_arch(),
+    }
+  }
+
+  // whether this source is a flat repository, whose suite is an exact path such as `./`.
+  // Parsers give `Component::NULL` only to sources whose suite ends with `/`.
+  pub fn is_flat(&self) -> bool {
+    self.component == Component::NULL
+  }
+
+  // split this source into sources of each architecture to fetch.
+  // Architectures are given by `arch` option, or all of dpkg architectures if not given.
+  // Flat repository has a single index for all architectures, so it's never split.
+  pub fn per_arch(&self, archs: &Architectures) -> Vec<Self> {
+    if self.archive_type != ArchivedType::DEB || self.is_flat() {
+      return vec![self.clone()];
+    }
+    let targets = match &self.options.arch {
+      Some(targets) if !targets.is_empty() => targets.clone(),
+      _ => archs.all(),
+    };
+    targets
+      .into_iter()
+      .map(|arch| {
+        let mut source = self.clone();
+        source.options.arch = Some(vec![arch]);
+        source
       })
       .collect()
   }
 
+  // whether `InRelease` of this source is trusted without signature check.
+  pub fn is_trusted(&self) -> bool {
+    self.options.trusted == Some(true)
+  }
+
+  // base URLs of this source in the order to try: `url` first, then fallback mirrors.
+  pub fn base_urls(&self) -> Vec<String> {
+    let mut urls = vec![self.url.clone()];
+    urls.extend(self.mirrors.iter().cloned());
+    urls
+  }
+
+  // the same source served by another mirror, which is used to build URLs to fetch.
+  // NOTE: cache filenames must be derived from the original source.
+  pub fn mirrored(&self, url: &str) -> Self {
+    Self {
+      url: url.into(),
+      ..self.clone()
+    }
+  }
+
   pub fn inrelease_url(&self) -> String {
     let mut url = self.url.as_str();
     let mut distro = self.distro.as_str();
@@ -111,7 +220,12 @@ impl Source {
       distro = tmp.as_str();
     }
 
-    format!("{}/dists/{}/InRelease", url, distro)
+    // `InRelease` of flat repository is placed at the exact path of the suite.
+    if self.is_flat() {
+      format!("{}/{}/InRelease", url, distro)
+    } else {
+      format!("{}/dists/{}/InRelease", url, distro)
+    }
   }
 
   pub fn packages_url(&self) -> String {
@@ -128,19 +242,12 @@ impl Source {
       tmp.next_back().unwrap();
       distro = tmp.as_str();
     }
-    let type_str = match self.archive_type {
-      ArchivedType::DEB => "binary-amd64",
-      ArchivedType::DEBSRC => "source",
-    };
-    let filename = match self.archive_type {
-      ArchivedType::DEB => "Packages",
-      ArchivedType::DEBSRC => "Sources",
-    };
-    if self.component == Component::NULL {
-      format!("{}/{}/{}.gz", url, distro, filename,)
+    let (type_str, filename) = self.index_type_and_name();
+    if self.is_flat() {
+      format!("{}/{}/{}", url, distro, filename,)
     } else {
       format!(
-        "{}/dists/{}/{}/{}/{}.gz",
+        "{}/dists/{}/{}/{}/{}",
         url,
         distro,
         self.component.to_string(),
@@ -150,17 +257,43 @@ impl Source {
     }
   }
 
+  // path of `Packages` index relative to the dist, which is listed in `InRelease`.
+  // NOTE: extension of compressed variants such as `.xz` is not included.
+  pub fn packages_index_path(&self) -> String {
+    let (type_str, filename) = self.index_type_and_name();
+    if self.is_flat() {
+      filename.to_string()
+    } else {
+      format!("{}/{}/{}", self.component, type_str, filename)
+    }
+  }
+
+  fn index_type_and_name(&self) -> (String, &'static str) {
+    match self.archive_type {
+      ArchivedType::DEB => (format!("binary-{}", self.arch()), "Packages"),
+      ArchivedType::DEBSRC => ("source".into(), "Sources"),
+    }
+  }
+
   pub fn cache_filename(&self) -> String {
-    let text = String::from(self.packages_url().split("://").collect::<Vec<&str>>()[1]);
-    text.replace("/", "_")[..text.len() - 3].into()
+    filename_of(&self.packages_url())
   }
 
   pub fn inrelease_filename(&self) -> String {
-    let text = String::from(self.inrelease_url().split("://").collect::<Vec<&str>>()[1]);
-    text.replace("/", "_")
+    filename_of(&self.inrelease_url())
   }
 }
 
+// cache filename of `url` without its scheme.
+// Local URIs have no host, eg) `file:/srv/repo/./Packages` => `srv_repo_._Packages`.
+fn filename_of(url: &str) -> String {
+  let path = match url.split_once("://") {
+    Some((_, path)) => path,
+    None => url.split_once(':').map(|(_, path)| path).unwrap_or(url),
+  };
+  path.trim_start_matches('/').replace("/", "_")
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -190,4 +323,78 @@ mod tests {
     assert_ne!(source1, source2);
     assert_eq!(source2, source3);
   }
+
+  #[test]
+  fn test_packages_index_path() {
+    let sources: Vec<Source> = Source::from(
+      ArchivedType::DEB,
+      "http://jp.archive.ubuntu.com/ubuntu/",
+      "focal",
+      vec![Component::MAIN],
+    )
+    .into_iter()
+    .collect();
+    assert_eq!(
+      sources[0].packages_index_path(),
+      format!("main/binary-{}/Packages", native_arch())
+    );
+  }
+
+  #[test]
+  fn test_local_cache_filename() {
+    let sources: Vec<Source> = Source::from(
+      ArchivedType::DEB,
+      "file:/srv/repo/",
+      "./",
+      vec![Component::NULL],
+    )
+    .into_iter()
+    .collect();
+    assert_eq!(sources[0].packages_url(), "file:/srv/repo/./Packages");
+    assert_eq!(sources[0].cache_filename(), "srv_repo_._Packages");
+    assert_eq!(sources[0].inrelease_filename(), "srv_repo_._InRelease");
+  }
+
+  #[test]
+  fn test_per_arch() {
+    let archs = Architectures {
+      native: "amd64".into(),
+      foreign: vec!["i386".into()],
+    };
+    let source = Source {
+      archive_type: ArchivedType::DEB,
+      url: "http://jp.archive.ubuntu.com/ubuntu/".into(),
+      distro: "focal".into(),
+      component: Component::MAIN,
+      options: Default::default(),
+      mirrors: vec![],
+    };
+    let paths: Vec<String> = source
+      .per_arch(&archs)
+      .iter()
+      .map(|source| source.packages_index_path())
+      .collect();
+    assert_eq!(
+      paths,
+      vec!["main/binary-amd64/Packages", "main/binary-i386/Packages"]
+    );
+
+    // `arch` option limits architectures to fetch.
+    let mut restricted = source.clone();
+    restricted.options.arch = Some(vec!["arm64".into()]);
+    let sources = restricted.per_arch(&archs);
+    assert_eq!(sources.len(), 1);
+    assert_eq!(
+      sources[0].packages_index_path(),
+      "main/binary-arm64/Packages"
+    );
+
+    // flat repository is not split.
+    let flat = Source {
+      distro: "/".into(),
+      component: Component::NULL,
+      ..source
+    };
+    assert_eq!(flat.per_arch(&archs), vec![flat.clone()]);
+  }
 }
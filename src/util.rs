@@ -2,11 +2,13 @@
  This file implements misc helper functions.
 */
 
+use crate::algorithm::conflict::ConflictPlan;
 use crate::command::error::RaptError;
+use crate::net::config::NetConfig;
 use crate::package::client::PackageWithSource;
 use crate::package::package::DepType;
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
 use console::style;
 use fs2::FileExt;
 use indicatif::{ProgressBar, ProgressStyle};
@@ -155,11 +157,16 @@ pub fn ami_root() -> bool {
 // convert SystemTime into `If-Modified-Since` format string.
 pub fn timestamp2ims(t: SystemTime) -> String {
   let secs = t.duration_since(UNIX_EPOCH).unwrap().as_secs();
-  let naive = NaiveDateTime::from_timestamp(secs as i64, 0);
-  let utc: DateTime<Utc> = DateTime::from_utc(naive, Utc);
+  let utc: DateTime<Utc> = DateTime::from_timestamp(secs as i64, 0).unwrap();
   utc.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
 }
 
+// convert `Last-Modified` format string into SystemTime.
+pub fn ims2timestamp(s: &str) -> Option<SystemTime> {
+  let secs = DateTime::parse_from_rfc2822(s).ok()?.timestamp();
+  Some(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+}
+
 pub fn default_progbar(len: u64) -> ProgressBar {
   let prog_style = ProgressStyle::default_bar()
     .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
@@ -239,3 +246,46 @@ pub fn show_deps_verbose(layers: &[Vec<PackageWithSource>]) {
     }
   }
 }
+
+// show proxies, timeouts and retries used by downloads.
+pub fn show_net_config_verbose(config: &NetConfig) {
+  println!("{}", style("Network configuration:").dim());
+  for line in config.to_string().lines() {
+    println!("\t- {}", style(line).dim());
+  }
+}
+
+// show installed packages removed/deconfigured by `Conflicts` and `Breaks`.
+pub fn show_conflict_plan(plan: &ConflictPlan) {
+  if !plan.removals.is_empty() {
+    println!(
+      "  {} Removed ({}):",
+      emoji::EMOJI_CROSS,
+      style(plan.removals.len()).bold().cyan()
+    );
+    for removal in &plan.removals {
+      println!(
+        "\t - {} ({}, conflicts with {}{})",
+        style(&removal.package.name).red(),
+        style(&removal.package.version).dim(),
+        style(&removal.cause).yellow(),
+        if removal.replaced { ", replaced" } else { "" }
+      );
+    }
+  }
+  if !plan.deconfigurations.is_empty() {
+    println!(
+      "  {} Deconfigured ({}):",
+      emoji::EMOJI_EXC,
+      style(plan.deconfigurations.len()).bold().cyan()
+    );
+    for deconf in &plan.deconfigurations {
+      println!(
+        "\t - {} ({}, broken by {})",
+        style(&deconf.package.name).red(),
+        style(&deconf.package.version).dim(),
+        style(&deconf.cause).yellow()
+      );
+    }
+  }
+}
@@ -3,11 +3,23 @@ extern crate rapt2;
 mod helper;
 
 use rapt2::{
-  algorithm::dag::{sort_depends, split_layers},
-  package::client::PackageClient,
+  algorithm::dag::{sort_depends, sort_depends_multi, split_layers},
+  apt::preferences::CandidatePolicy,
+  dpkg::{
+    arch::native_arch,
+    client::{DpkgClient, StatusComp},
+    status::DpkgStatusArea,
+  },
+  package::{
+    client::{PackageClient, PackageWithSource},
+    error::PackageError,
+    package::Package,
+    version::Version,
+  },
   source::source::*,
 };
 
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 #[test]
@@ -27,10 +39,12 @@ fn test_resolve_deps() {
     url: "http://test3".into(),
     distro: "/".into(),
     component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
   };
   let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
   let deps = client
-    .get_package_with_deps("0", &vec![source], true, None)
+    .get_package_with_deps("0", &vec![source], true, false, None)
     .unwrap();
 
   // check if dependencies are correctly gathered from list files.
@@ -92,10 +106,12 @@ fn test_predeps() {
     url: "http://test4".into(),
     distro: "/".into(),
     component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
   };
   let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
   let deps = client
-    .get_package_with_deps("1", &vec![source], true, None)
+    .get_package_with_deps("1", &vec![source], true, false, None)
     .unwrap();
 
   let sorted_deps = sort_depends(deps.clone(), "1").unwrap();
@@ -111,3 +127,531 @@ fn test_predeps() {
     panic!();
   }
 }
+
+#[test]
+fn test_alternative_deps() {
+  /*
+    a -> missing | b
+      -> c (>= 2.0) | d     (c is 1.0)
+    e -> f | gcc            (gcc is installed)
+    g -> missing | missing2
+  */
+  let source = Source {
+    archive_type: ArchivedType::DEB,
+    url: "http://test7".into(),
+    distro: "/".into(),
+    component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
+  };
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+
+  // choose alternatives which exist and satisfy version constraints.
+  let deps = client
+    .get_package_with_deps("a", &vec![source.clone()], true, false, None)
+    .unwrap();
+  let mut names: Vec<String> = deps.iter().map(|pws| pws.package.name.clone()).collect();
+  names.sort();
+  assert_eq!(names, vec!["a", "b", "d"]);
+  let sorted_deps = sort_depends(deps, "a").unwrap();
+  assert_eq!(sorted_deps[0].package.name, "a");
+
+  // prefer already installed alternative.
+  let mut dpkg_client = DpkgClient::new(
+    PathBuf::from("tests/resources/dpkg"),
+    PathBuf::from("tests/resources/extended_states"),
+  );
+  let deps = client
+    .get_package_with_deps(
+      "e",
+      &vec![source.clone()],
+      false,
+      false,
+      Some(&mut dpkg_client),
+    )
+    .unwrap();
+  assert_eq!(deps.len(), 1);
+  assert_eq!(deps.into_iter().next().unwrap().package.name, "e");
+
+  // fail only when no alternative is satisfiable.
+  assert!(client
+    .get_package_with_deps("g", &vec![source], true, false, None)
+    .is_err());
+}
+
+#[test]
+fn test_versioned_deps() {
+  /*
+    test8: p -> q (<< 2.0), r (>= 1.0)
+           r -> q (>= 1.0)
+           s -> t, u
+           t -> q
+           u -> q (<< 2.0)
+           w -> q (>= 3.0)
+    versions of q: 2.0 (test8), 1.5 (test9)
+  */
+  let sources: Vec<Source> = vec!["http://test8", "http://test9"]
+    .into_iter()
+    .map(|url| Source {
+      archive_type: ArchivedType::DEB,
+      url: url.into(),
+      distro: "/".into(),
+      component: Component::NULL,
+      options: Default::default(),
+      mirrors: vec![],
+    })
+    .collect();
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+  let version_of = |deps: &HashSet<PackageWithSource>, name: &str| {
+    deps
+      .iter()
+      .find(|pws| pws.package.name == name)
+      .unwrap()
+      .package
+      .version
+      .clone()
+  };
+
+  // choose the highest version satisfying all constraints across sources.
+  let deps = client
+    .get_package_with_deps("p", &sources, true, false, None)
+    .unwrap();
+  assert_eq!(deps.len(), 3);
+  assert_eq!(version_of(&deps, "q"), Version::from("1.5-1").unwrap());
+  assert_eq!(version_of(&deps, "r"), Version::from("1.0-1").unwrap());
+
+  // re-choose already chosen package when a later constraint conflicts.
+  let deps = client
+    .get_package_with_deps("s", &sources, true, false, None)
+    .unwrap();
+  assert_eq!(deps.len(), 4);
+  assert_eq!(version_of(&deps, "q"), Version::from("1.5-1").unwrap());
+
+  // fail when no version satisfies the constraint.
+  match client.get_package_with_deps("w", &sources, true, false, None) {
+    Err(PackageError::UnresolvedDependency {
+      depended_on,
+      depending_on,
+      ..
+    }) => {
+      assert_eq!(depended_on, "q");
+      assert_eq!(depending_on, "w");
+    }
+    _ => panic!(),
+  }
+
+  // installed q 2.5 doesn't satisfy `q (<< 2.0)`, so q must be downgraded.
+  let mut dpkg_client = DpkgClient::new(
+    PathBuf::from("tests/resources/dpkg"),
+    PathBuf::from("tests/resources/extended_states"),
+  );
+  let deps = client
+    .get_package_with_deps("p", &sources, false, false, Some(&mut dpkg_client))
+    .unwrap();
+  let q = deps.iter().find(|pws| pws.package.name == "q").unwrap();
+  assert_eq!(q.package.version, Version::from("1.5-1").unwrap());
+  assert_eq!(
+    q.dpkg_status,
+    Some(StatusComp::NEWER(Version::from("2.5-1").unwrap()))
+  );
+}
+
+#[test]
+fn test_virtual_deps() {
+  /*
+    mta-user    -> mail-transport-agent   (provided by postfix, exim4)
+    awk-user    -> awk (>= 1.3)           (provided by gawk, mawk (= 1.3.4))
+    editor-user -> editor                 (provided by nano, and installed vim)
+  */
+  let source = Source {
+    archive_type: ArchivedType::DEB,
+    url: "http://test10".into(),
+    distro: "/".into(),
+    component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
+  };
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+  let names_of = |deps: &HashSet<PackageWithSource>| {
+    let mut names: Vec<String> = deps.iter().map(|pws| pws.package.name.clone()).collect();
+    names.sort();
+    names
+  };
+
+  // choose one of providers.
+  let deps = client
+    .get_package_with_deps("mta-user", &vec![source.clone()], true, false, None)
+    .unwrap();
+  assert_eq!(names_of(&deps), vec!["exim4", "mta-user"]);
+  let sorted_deps = sort_depends(deps, "mta-user").unwrap();
+  assert_eq!(sorted_deps[0].package.name, "mta-user");
+
+  // versioned dependency is satisfied only by versioned provides.
+  let deps = client
+    .get_package_with_deps("awk-user", &vec![source.clone()], true, false, None)
+    .unwrap();
+  assert_eq!(names_of(&deps), vec!["awk-user", "mawk"]);
+
+  // prefer already installed provider.
+  let mut dpkg_client = DpkgClient::new(
+    PathBuf::from("tests/resources/dpkg"),
+    PathBuf::from("tests/resources/extended_states"),
+  );
+  let deps = client
+    .get_package_with_deps(
+      "editor-user",
+      &vec![source],
+      false,
+      false,
+      Some(&mut dpkg_client),
+    )
+    .unwrap();
+  assert_eq!(names_of(&deps), vec!["editor-user"]);
+}
+
+#[test]
+fn test_recommends_deps() {
+  /*
+    rec-user -> rec-lib
+             -> (recommends) rec-extra -> rec-extra-lib
+             -> (recommends) rec-broken (unresolvable) | rec-missing (not found)
+             -> (suggests)   rec-doc
+  */
+  let source = Source {
+    archive_type: ArchivedType::DEB,
+    url: "http://test11".into(),
+    distro: "/".into(),
+    component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
+  };
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+  let names_of = |deps: &HashSet<PackageWithSource>| {
+    let mut names: Vec<String> = deps.iter().map(|pws| pws.package.name.clone()).collect();
+    names.sort();
+    names
+  };
+
+  // recommends are not installed without the flag.
+  let deps = client
+    .get_package_with_deps("rec-user", &vec![source.clone()], true, false, None)
+    .unwrap();
+  assert_eq!(names_of(&deps), vec!["rec-lib", "rec-user"]);
+
+  // unresolvable recommends are just skipped, and suggests are never installed.
+  let deps = client
+    .get_package_with_deps("rec-user", &vec![source], true, true, None)
+    .unwrap();
+  assert_eq!(
+    names_of(&deps),
+    vec!["rec-extra", "rec-extra-lib", "rec-lib", "rec-user"]
+  );
+  let sorted_deps = sort_depends(deps, "rec-user").unwrap();
+  assert_eq!(sorted_deps.len(), 4);
+  assert_eq!(sorted_deps[0].package.name, "rec-user");
+}
+
+#[test]
+fn test_multiple_targets() {
+  let source = Source {
+    archive_type: ArchivedType::DEB,
+    url: "http://test10".into(),
+    distro: "/".into(),
+    component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
+  };
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+  let targets = vec!["mta-user".to_string(), "awk-user".to_string()];
+
+  // union of dependencies is resolved and sorted at once.
+  let deps = client
+    .get_packages_with_deps(&targets, &vec![source], true, false, None)
+    .unwrap();
+  let mut names: Vec<String> = deps.iter().map(|pws| pws.package.name.clone()).collect();
+  names.sort();
+  assert_eq!(names, vec!["awk-user", "exim4", "mawk", "mta-user"]);
+  let sorted_deps = sort_depends_multi(deps, &targets).unwrap();
+  assert_eq!(sorted_deps.len(), 4);
+  let position = |name: &str| {
+    sorted_deps
+      .iter()
+      .position(|pws| pws.package.name == name)
+      .unwrap()
+  };
+  // depending packages come first cuz the result is in reversed order.
+  assert!(position("mta-user") < position("exim4"));
+  assert!(position("awk-user") < position("mawk"));
+}
+
+#[test]
+fn test_pinned_targets() {
+  /*
+    pin-tool 3.0-1 (backports)
+    pin-tool 2.0-1 -> pin-lib (>= 2.0)
+    pin-tool 1.0-1 -> pin-lib
+  */
+  let sources = vec![
+    Source {
+      archive_type: ArchivedType::DEB,
+      url: "http://test12".into(),
+      distro: "/".into(),
+      component: Component::NULL,
+      options: Default::default(),
+      mirrors: vec![],
+    },
+    Source {
+      archive_type: ArchivedType::DEB,
+      url: "http://test12".into(),
+      distro: "backports".into(),
+      component: Component::NULL,
+      options: Default::default(),
+      mirrors: vec![],
+    },
+  ];
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+  let version_of = |deps: &HashSet<PackageWithSource>| {
+    deps
+      .iter()
+      .find(|pws| pws.package.name == "pin-tool")
+      .unwrap()
+      .package
+      .version
+      .clone()
+  };
+
+  // the highest version by default.
+  let deps = client
+    .get_package_with_deps("pin-tool", &sources, true, false, None)
+    .unwrap();
+  assert_eq!(version_of(&deps), Version::from("3.0-1").unwrap());
+
+  // pinned by version.
+  let deps = client
+    .get_package_with_deps("pin-tool=2.0-1", &sources, true, false, None)
+    .unwrap();
+  assert_eq!(version_of(&deps), Version::from("2.0-1").unwrap());
+  assert_eq!(deps.len(), 2);
+
+  // pinned by release.
+  let deps = client
+    .get_package_with_deps("pin-tool/backports", &sources, true, false, None)
+    .unwrap();
+  assert_eq!(version_of(&deps), Version::from("3.0-1").unwrap());
+  assert_eq!(deps.iter().next().unwrap().source.distro, "backports");
+
+  // unknown version or release.
+  assert!(matches!(
+    client.get_package_with_deps("pin-tool=9.9-1", &sources, true, false, None),
+    Err(PackageError::PackageNotFound { .. })
+  ));
+  assert!(matches!(
+    client.get_package_with_deps("pin-tool/unknown", &sources, true, false, None),
+    Err(PackageError::PackageNotFound { .. })
+  ));
+  // invalid epoch is rejected instead of panicking.
+  assert!(matches!(
+    client.get_package_with_deps("pin-tool=a:1-1", &sources, true, false, None),
+    Err(PackageError::InvalidPackageName { .. })
+  ));
+}
+
+#[test]
+fn test_candidate_policy() {
+  let sources = vec![
+    Source {
+      archive_type: ArchivedType::DEB,
+      url: "http://test12".into(),
+      distro: "/".into(),
+      component: Component::NULL,
+      options: Default::default(),
+      mirrors: vec![],
+    },
+    Source {
+      archive_type: ArchivedType::DEB,
+      url: "http://test12".into(),
+      distro: "backports".into(),
+      component: Component::NULL,
+      options: Default::default(),
+      mirrors: vec![],
+    },
+  ];
+  let policy = CandidatePolicy::read(
+    &PathBuf::from("tests/resources/apt"),
+    &sources,
+    &PathBuf::from("tests/resources/lists"),
+  )
+  .unwrap();
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+  client.set_policy(policy);
+
+  // backports has lower priority than the default.
+  let deps = client
+    .get_package_with_deps("pin-tool", &sources, true, false, None)
+    .unwrap();
+  let pin_tool = deps
+    .iter()
+    .find(|pws| pws.package.name == "pin-tool")
+    .unwrap();
+  assert_eq!(pin_tool.package.version, Version::from("2.0-1").unwrap());
+  let candidates = client
+    .read_all_from_source_with_source(&sources, &HashSet::new())
+    .unwrap();
+  let pin_tool = candidates
+    .iter()
+    .find(|pws| pws.package.name == "pin-tool")
+    .unwrap();
+  assert_eq!(pin_tool.package.version, Version::from("2.0-1").unwrap());
+
+  // installed backports version is kept cuz downgrade needs priority 1000 or higher.
+  let installed = Package {
+    name: "pin-tool".into(),
+    version: Version::from("3.0-1").unwrap(),
+    status: Some(DpkgStatusArea::from("install ok installed")),
+    ..Default::default()
+  };
+  let candidates = client
+    .read_all_from_source_with_source(&sources, &vec![installed].into_iter().collect())
+    .unwrap();
+  let pin_tool = candidates
+    .iter()
+    .find(|pws| pws.package.name == "pin-tool")
+    .unwrap();
+  assert_eq!(pin_tool.package.version, Version::from("3.0-1").unwrap());
+
+  // versions with negative priority are never selected.
+  assert!(matches!(
+    client.get_package_with_deps("pin-tool=1.0-1", &sources, true, false, None),
+    Err(PackageError::PackageNotFound { .. })
+  ));
+}
+
+#[test]
+fn test_multiarch_deps() {
+  /*
+    app (amd64, i386) -> libfoo (M-A: same), tool (M-A: foreign), python3:any (M-A: allowed)
+    broken (i386) -> libbar (amd64 only, M-A: no)
+  */
+  if native_arch() != "amd64" {
+    return; // the fixture assumes amd64 as native architecture
+  }
+  let source = Source {
+    archive_type: ArchivedType::DEB,
+    url: "http://test13".into(),
+    distro: "/".into(),
+    component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
+  };
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+  let full_names = |deps: &HashSet<PackageWithSource>| {
+    let mut names: Vec<String> = deps.iter().map(|pws| pws.package.full_name()).collect();
+    names.sort();
+    names
+  };
+
+  // `Multi-Arch: same` library is resolved in the same architecture,
+  // while `Multi-Arch: foreign` and `:any` dependencies are satisfied by native ones.
+  let deps = client
+    .get_package_with_deps("app:i386", &vec![source.clone()], true, false, None)
+    .unwrap();
+  assert_eq!(
+    full_names(&deps),
+    vec!["app:i386", "libfoo:i386", "python3", "tool"]
+  );
+
+  // both architectures are co-installed in one transaction.
+  let targets = vec!["app".to_string(), "app:i386".to_string()];
+  let deps = client
+    .get_packages_with_deps(&targets, &vec![source.clone()], true, false, None)
+    .unwrap();
+  assert_eq!(
+    full_names(&deps),
+    vec![
+      "app",
+      "app:i386",
+      "libfoo",
+      "libfoo:i386",
+      "python3",
+      "tool"
+    ]
+  );
+  let sorted_deps = sort_depends_multi(deps, &targets).unwrap();
+  assert_eq!(sorted_deps.len(), 6);
+  let position = |name: &str| {
+    sorted_deps
+      .iter()
+      .position(|pws| pws.package.full_name() == name)
+      .unwrap()
+  };
+  assert!(position("app:i386") < position("libfoo:i386"));
+  assert!(position("app") < position("libfoo"));
+
+  // native package which is not `Multi-Arch: foreign` can't satisfy foreign one.
+  assert!(client
+    .get_package_with_deps("broken:i386", &vec![source], true, false, None)
+    .is_err());
+}
+
+#[test]
+fn test_versions_in_single_index() {
+  // an index lists multi 2.0-1 and 1.0-1, where only 2.0-1 depends on multi-lib.
+  let sources = vec![Source {
+    archive_type: ArchivedType::DEB,
+    url: "http://test14".into(),
+    distro: "/".into(),
+    component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
+  }];
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+
+  let versions = client
+    .read_all_versions_with_source("multi", &sources)
+    .unwrap();
+  let versions: Vec<Version> = versions
+    .into_iter()
+    .map(|pws| pws.package.version)
+    .collect();
+  assert_eq!(
+    versions,
+    vec![
+      Version::from("2.0-1").unwrap(),
+      Version::from("1.0-1").unwrap()
+    ]
+  );
+
+  let deps = client
+    .get_package_with_deps("multi", &sources, true, false, None)
+    .unwrap();
+  assert_eq!(deps.len(), 2);
+
+  // the older version in the same index can be pinned.
+  let deps = client
+    .get_package_with_deps("multi=1.0-1", &sources, true, false, None)
+    .unwrap();
+  assert_eq!(deps.len(), 1);
+  assert_eq!(
+    deps.into_iter().next().unwrap().package.version,
+    Version::from("1.0-1").unwrap()
+  );
+}
+
+#[test]
+fn test_deps_without_dpkg_client() {
+  let sources = vec![Source {
+    archive_type: ArchivedType::DEB,
+    url: "http://test14".into(),
+    distro: "/".into(),
+    component: Component::NULL,
+    options: Default::default(),
+    mirrors: vec![],
+  }];
+  let mut client = PackageClient::new(PathBuf::from("tests/resources/lists")).unwrap();
+
+  // nothing is regarded as installed without dpkg client.
+  let deps = client
+    .get_package_with_deps("multi", &sources, false, false, None)
+    .unwrap();
+  assert_eq!(deps.len(), 2);
+}
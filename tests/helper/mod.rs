@@ -56,6 +56,25 @@ pub fn sources_list_fuga() -> HashSet<Source> {
   )
 }
 
+// Source contents in `tests/resources/sources.list.d/piyo.sources`.
+pub fn sources_list_piyo() -> HashSet<Source> {
+  Source::from(
+    ArchivedType::DEB,
+    "http://jp.archive.ubuntu.com/ubuntu/",
+    "focal-security",
+    vec![Component::MAIN, Component::UNIVERSE],
+  )
+  .into_iter()
+  .map(|source| Source {
+    options: SourceOptions {
+      signed_by: Some("/usr/share/keyrings/ubuntu-archive-keyring.gpg".into()),
+      ..Default::default()
+    },
+    ..source
+  })
+  .collect()
+}
+
 // Package contents in `tests/resources/lists/cache/test1_Packages`.
 pub fn package_list_test1() -> HashSet<Package> {
   vec![
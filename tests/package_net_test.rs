@@ -1,38 +1,283 @@
 extern crate rapt2;
 
 use rapt2::{
-  net::package::PackageDownloadClient,
+  context::Context,
+  net::{
+    binary::BinaryDownloader,
+    checksum::sha256_hex,
+    config::NetConfig,
+    mock::MockTransport,
+    package::PackageDownloadClient,
+    pool::{DownloadPool, DEFAULT_JOBS},
+    transport::DefaultTransport,
+  },
+  package::{client::PackageWithSource, package::Package},
   source::{
     client::SourceClient,
-    source::{ArchivedType, Source},
+    source::{ArchivedType, Component, Source, SourceOptions},
   },
 };
 
+use flate2::{write::GzEncoder, Compression};
+use once_cell::sync::OnceCell;
+use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 mod helper;
 
-//#[test]
-#[allow(dead_code)]
-fn test_inrelease_net() {
+// `InRelease` listing `files` of (path relative to the dist, content).
+fn inrelease(files: &[(String, &[u8])]) -> String {
+  let mut content = String::from("Origin: Ubuntu\nSuite: focal\nSHA256:\n");
+  for (path, bytes) in files {
+    content += &format!(" {} {} {}\n", sha256_hex(bytes), bytes.len(), path);
+  }
+  content
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("rapt2-{}-{}", name, std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+// sources of `sources.list` served by a mirror, whose `InRelease` is trusted.
+fn trusted_sources() -> Vec<Source> {
   let source_client = SourceClient::new(PathBuf::from("./tests/resources/sources")).unwrap();
-  let sources: Vec<Source> = source_client
+  let mut sources: Vec<Source> = source_client
     .read_single_file("sources.list")
     .unwrap()
-    .into_iter()
-    .collect();
-  let sources = sources
     .into_iter()
     .filter(|source| source.archive_type == ArchivedType::DEB)
     .collect();
+  sources.sort_by_key(|source| source.component.to_string());
+  for source in &mut sources {
+    source.options.trusted = Some(true);
+  }
+  sources
+}
+
+#[test]
+fn test_fetch_index_mock() {
+  let sources = trusted_sources();
+  let (main, restricted) = (&sources[0], &sources[1]);
+  assert_eq!(main.component, Component::MAIN);
+  let main_packages = fs::read("./tests/resources/index/Packages").unwrap();
+  let main_xz = fs::read("./tests/resources/index/Packages.xz").unwrap();
+  let restricted_packages = fs::read("./tests/resources/repo/Packages").unwrap();
+
+  // `main` is served as `.xz`, and `restricted` is served uncompressed.
+  let transport = Arc::new(MockTransport::new());
+  transport.put(
+    &main.inrelease_url(),
+    inrelease(&[
+      (main.packages_index_path(), &main_packages),
+      (format!("{}.xz", main.packages_index_path()), &main_xz),
+      (restricted.packages_index_path(), &restricted_packages),
+    ]),
+  );
+  transport.put(&format!("{}.xz", main.packages_url()), main_xz.clone());
+  transport.put(&restricted.packages_url(), restricted_packages.clone());
+  let context = Context {
+    net_config: OnceCell::with_value(NetConfig::default()),
+    transport: Some(transport.clone()),
+    ..Default::default()
+  };
+  let cache_dir = temp_dir("mock-index");
 
   let mut package_client =
-    PackageDownloadClient::new(sources, PathBuf::from("./rapt2/lists")).unwrap();
-  // update InRelease
-  loop {
-    if package_client.get_inrelease_ifneed().unwrap() == false {
-      break;
-    }
-  }
-  println!("{:?}", package_client);
+    PackageDownloadClient::new(sources.clone(), cache_dir.clone(), &cache_dir).unwrap();
+  let pool = context.download_pool().unwrap();
+  package_client.fetch_all_inrelease(&pool).unwrap();
+  let mut contents = package_client.fetch_all_packages(&pool).unwrap();
+  contents.sort();
+  let mut expected = vec![
+    String::from_utf8(main_packages).unwrap(),
+    String::from_utf8(restricted_packages).unwrap(),
+  ];
+  expected.sort();
+  assert_eq!(contents, expected);
+  assert_eq!(transport.requests().len(), 3);
+
+  // `InRelease` is not modified, so `Packages` are read from cache.
+  transport.clear_requests();
+  let mut package_client =
+    PackageDownloadClient::new(sources.clone(), cache_dir.clone(), &cache_dir).unwrap();
+  package_client.fetch_all_inrelease(&pool).unwrap();
+  let mut contents = package_client.fetch_all_packages(&pool).unwrap();
+  contents.sort();
+  assert_eq!(contents, expected);
+  assert_eq!(transport.requests(), vec![main.inrelease_url()]);
+
+  fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[test]
+fn test_fetch_index_without_inrelease_mock() {
+  let sources: Vec<Source> = trusted_sources().into_iter().take(1).collect();
+  let source = &sources[0];
+  let packages = fs::read("./tests/resources/index/Packages").unwrap();
+  let mut encoder = GzEncoder::new(vec![], Compression::default());
+  encoder.write_all(&packages).unwrap();
+
+  // trusted source without `InRelease` is probed in the order of `.xz`, `.gz` and plain.
+  let transport = Arc::new(MockTransport::new());
+  transport.put(
+    &format!("{}.gz", source.packages_url()),
+    encoder.finish().unwrap(),
+  );
+  let pool = DownloadPool::new(DEFAULT_JOBS, transport.clone());
+  let cache_dir = temp_dir("no-inrelease");
+
+  let mut package_client =
+    PackageDownloadClient::new(sources.clone(), cache_dir.clone(), &cache_dir).unwrap();
+  package_client.fetch_all_inrelease(&pool).unwrap();
+  let contents = package_client.fetch_all_packages(&pool).unwrap();
+  assert_eq!(contents, vec![String::from_utf8(packages).unwrap()]);
+  assert_eq!(
+    transport.requests(),
+    vec![
+      source.inrelease_url(),
+      format!("{}.xz", source.packages_url()),
+      format!("{}.gz", source.packages_url()),
+    ]
+  );
+
+  // untrusted source needs `InRelease`.
+  let mut untrusted = sources.clone();
+  untrusted[0].options.trusted = None;
+  let mut package_client =
+    PackageDownloadClient::new(untrusted, cache_dir.clone(), &cache_dir).unwrap();
+  assert!(package_client.fetch_all_inrelease(&pool).is_err());
+
+  fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[test]
+fn test_pdiff_mock() {
+  let sources: Vec<Source> = trusted_sources().into_iter().take(1).collect();
+  let source = &sources[0];
+  let old = "Package: a\nVersion: 1\n\nPackage: b\nVersion: 1\n";
+  let new = "Package: a\nVersion: 2\n\nPackage: b\nVersion: 1\n";
+  let patch = "2c\nVersion: 2\n.\n";
+  let mut encoder = GzEncoder::new(vec![], Compression::default());
+  encoder.write_all(patch.as_bytes()).unwrap();
+  let patch_gz = encoder.finish().unwrap();
+
+  let transport = Arc::new(MockTransport::new());
+  let pool = DownloadPool::new(DEFAULT_JOBS, transport.clone());
+  let cache_dir = temp_dir("mock-pdiff");
+
+  // first, cache old `Packages`.
+  transport.put(
+    &source.inrelease_url(),
+    inrelease(&[(source.packages_index_path(), old.as_bytes())]),
+  );
+  transport.put(&source.packages_url(), old);
+  let mut package_client =
+    PackageDownloadClient::new(sources.clone(), cache_dir.clone(), &cache_dir).unwrap();
+  package_client.fetch_all_inrelease(&pool).unwrap();
+  assert_eq!(package_client.fetch_all_packages(&pool).unwrap(), vec![old]);
+
+  // then, `Packages` is updated by a patch, and full `Packages` is never fetched.
+  let index = format!(
+    "SHA256-Current: {} {}\nSHA256-History:\n {} {} 2024-01-01-0000.00\nSHA256-Patches:\n {} {} 2024-01-01-0000.00\nSHA256-Download:\n {} {} 2024-01-01-0000.00.gz\n",
+    sha256_hex(new.as_bytes()),
+    new.len(),
+    sha256_hex(old.as_bytes()),
+    old.len(),
+    sha256_hex(patch.as_bytes()),
+    patch.len(),
+    sha256_hex(&patch_gz),
+    patch_gz.len(),
+  );
+  transport.put_modified(
+    &source.inrelease_url(),
+    inrelease(&[
+      (source.packages_index_path(), new.as_bytes()),
+      (
+        format!("{}.diff/Index", source.packages_index_path()),
+        index.as_bytes(),
+      ),
+    ]),
+    SystemTime::now() + Duration::from_secs(3600),
+  );
+  transport.remove(&source.packages_url());
+  transport.clear_requests();
+  transport.put(&format!("{}.diff/Index", source.packages_url()), index);
+  transport.put(
+    &format!("{}.diff/2024-01-01-0000.00.gz", source.packages_url()),
+    patch_gz,
+  );
+  let mut package_client =
+    PackageDownloadClient::new(sources.clone(), cache_dir.clone(), &cache_dir).unwrap();
+  package_client.fetch_all_inrelease(&pool).unwrap();
+  assert_eq!(package_client.fetch_all_packages(&pool).unwrap(), vec![new]);
+  assert!(!transport.requests().contains(&source.packages_url()));
+
+  fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[test]
+fn test_local_repository() {
+  let repo = fs::canonicalize("./tests/resources/repo").unwrap();
+  let source = Source {
+    archive_type: ArchivedType::DEB,
+    url: format!("file:{}/", repo.to_string_lossy()),
+    distro: "./".into(),
+    component: Component::NULL,
+    options: SourceOptions {
+      trusted: Some(true),
+      ..Default::default()
+    },
+    mirrors: vec![],
+  };
+  let cache_dir = std::env::temp_dir().join(format!("rapt2-local-test-{}", std::process::id()));
+  fs::create_dir_all(&cache_dir).unwrap();
+
+  // `InRelease` and `Packages` are read from the repository on disk.
+  let mut package_client = PackageDownloadClient::new(
+    vec![source.clone()],
+    cache_dir.join("lists"),
+    &PathBuf::from("./tests/resources/apt"),
+  )
+  .unwrap();
+  let pool = DownloadPool::new(DEFAULT_JOBS, Arc::new(DefaultTransport::default()));
+  package_client.fetch_all_inrelease(&pool).unwrap();
+  let contents = package_client.fetch_all_packages(&pool).unwrap();
+  assert_eq!(
+    contents,
+    vec![fs::read_to_string(repo.join("Packages")).unwrap()]
+  );
+  assert!(cache_dir
+    .join("lists")
+    .join(source.cache_filename())
+    .is_file());
+
+  // archives are copied into archive dir.
+  let package = Package {
+    name: "hello".into(),
+    filename: "./pool/hello_1.0_all.deb".into(),
+    size: 5,
+    sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".into(),
+    ..Default::default()
+  };
+  let downloader = BinaryDownloader::new(
+    vec![PackageWithSource {
+      package,
+      source,
+      dpkg_status: None,
+    }],
+    cache_dir.join("archives"),
+  )
+  .unwrap();
+  downloader.download_all(&pool).unwrap();
+  assert_eq!(
+    fs::read(cache_dir.join("archives").join("hello_1.0_all.deb")).unwrap(),
+    b"hello"
+  );
+
+  fs::remove_dir_all(&cache_dir).unwrap();
 }
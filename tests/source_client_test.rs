@@ -20,6 +20,7 @@ fn test_sourcelist_read() {
     helper::sources_list_sources(),
     helper::sources_list_hoge(),
     helper::sources_list_fuga(),
+    helper::sources_list_piyo(),
   ]
   .into_iter()
   .flatten()
@@ -28,4 +29,10 @@ fn test_sourcelist_read() {
   let sources = client.read_all().unwrap();
   let sources_hashset: HashSet<Source> = sources.into_iter().collect();
   assert_eq!(answer_set, sources_hashset);
+
+  // stanza with embedded `Signed-By` key is skipped without failing others.
+  let sources = client
+    .read_single_file("sources.list.d/inline-key.sources")
+    .unwrap();
+  assert!(sources.is_empty());
 }